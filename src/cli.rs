@@ -1,10 +1,78 @@
-use std::{fmt::Display, process::Command};
+use std::{
+    cell::Cell,
+    fmt::Display,
+    io::{IsTerminal, stdin},
+    process::Command,
+    sync::OnceLock,
+};
 
 use colored::Colorize;
 use dialoguer::{Input, Select};
 
 use crate::error::Result;
 
+static INPUT_POLICY: OnceLock<InputPolicy> = OnceLock::new();
+
+thread_local! {
+    /// Takes over the global policy on one thread, so tests don't decide it for each other
+    static THREAD_INPUT_POLICY: Cell<Option<InputPolicy>> = const { Cell::new(None) };
+}
+
+/// How handled errors and questions get resolved
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputPolicy {
+    /// Prompt the user
+    Ask,
+    /// Skip the failing step
+    Skip,
+    /// Try to fix the error without prompting, skip the step if it still fails
+    Fix,
+    /// Abort on the first handled error
+    Fail,
+}
+
+impl InputPolicy {
+    pub const VALUES: [&str; 3] = ["skip", "fix", "fail"];
+
+    /// The global policy, defaults to [`InputPolicy::Skip`] when stdin isn't a terminal
+    pub fn get() -> Self {
+        if let Some(policy) = THREAD_INPUT_POLICY.get() {
+            return policy;
+        }
+        *INPUT_POLICY.get_or_init(|| {
+            if stdin().is_terminal() {
+                Self::Ask
+            } else {
+                Self::Skip
+            }
+        })
+    }
+
+    /// Sets the global policy, only the first call has an effect
+    pub fn set(self) {
+        let _ = INPUT_POLICY.set(self);
+    }
+
+    /// Sets the policy of the current thread only, the global one is left alone
+    #[cfg(test)]
+    pub fn set_for_thread(self) {
+        THREAD_INPUT_POLICY.set(Some(self));
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "skip" => Some(Self::Skip),
+            "fix" => Some(Self::Fix),
+            "fail" => Some(Self::Fail),
+            _ => None,
+        }
+    }
+
+    pub fn is_interactive(&self) -> bool {
+        *self == Self::Ask
+    }
+}
+
 pub fn skip_dialog<F, R>(prompt: &str, item: &str, action: &str, on_action: F) -> Option<R>
 where
     F: Fn() -> R,
@@ -16,10 +84,9 @@ where
         .item(action)
         .interact_opt()
         .unwrap()
+        && picked == 1
     {
-        if picked == 1 {
-            return Some(on_action());
-        }
+        return Some(on_action());
     }
     None
 }
@@ -41,11 +108,28 @@ pub fn display_success(msg: impl Display) {
 }
 
 pub fn start_cmd(command: &str) -> Result<()> {
+    #[cfg(windows)]
     Command::new("cmd").args(["/C", "start", command]).spawn()?;
+    #[cfg(not(windows))]
+    Command::new("xdg-open").arg(command).spawn()?;
     Ok(())
 }
 
-pub fn wait_for_user(action: &str) {
+/// Opens a page for the user to act on, only printing it when nobody is there to see it
+pub fn open_for_user(target: &str, policy: InputPolicy) {
+    if !policy.is_interactive() {
+        return display_error(format!("Open {} to fix it", target));
+    }
+    if start_cmd(target).is_err() {
+        display_error(format!("Couldn't open {}, open it yourself", target));
+    }
+}
+
+pub fn wait_for_user(action: &str, policy: InputPolicy) {
+    if !policy.is_interactive() {
+        return;
+    }
+
     Input::new()
         .default(true)
         .with_prompt(format!("Waiting for {} press enter when done", action))
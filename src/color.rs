@@ -3,8 +3,49 @@ use schemars::{JsonSchema, json_schema};
 use hex_color::HexColor;
 use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize, Serialize)]
-pub struct RgbaHexColor(HexColor);
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct RgbaHexColor(#[serde(with = "hex_color::rgba")] pub HexColor);
+
+impl RgbaHexColor {
+    pub const fn new(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self(HexColor::rgba(r, g, b, a))
+    }
+
+    /// Blends towards `other`, `amount` going from 0 (self) to 1 (other)
+    pub fn mix(self, other: Self, amount: f32) -> Self {
+        let channel = |from: u8, to: u8| {
+            (from as f32 + (to as f32 - from as f32) * amount.clamp(0.0, 1.0)).round() as u8
+        };
+        Self::new(
+            channel(self.0.r, other.0.r),
+            channel(self.0.g, other.0.g),
+            channel(self.0.b, other.0.b),
+            channel(self.0.a, other.0.a),
+        )
+    }
+
+    /// Reads a registry DWORD stored as `0xAABBGGRR`
+    pub const fn from_abgr(value: u32) -> Self {
+        let [a, b, g, r] = value.to_be_bytes();
+        Self::new(r, g, b, a)
+    }
+
+    pub const fn to_abgr(self) -> u32 {
+        let HexColor { r, g, b, a } = self.0;
+        u32::from_be_bytes([a, b, g, r])
+    }
+
+    /// Reads a registry DWORD stored as `0xAARRGGBB`
+    pub const fn from_argb(value: u32) -> Self {
+        let [a, r, g, b] = value.to_be_bytes();
+        Self::new(r, g, b, a)
+    }
+
+    pub const fn to_argb(self) -> u32 {
+        let HexColor { r, g, b, a } = self.0;
+        u32::from_be_bytes([a, r, g, b])
+    }
+}
 
 impl JsonSchema for RgbaHexColor {
     fn schema_name() -> std::borrow::Cow<'static, str> {
@@ -18,7 +59,7 @@ impl JsonSchema for RgbaHexColor {
     }
 }
 
-#[derive(Deserialize, Serialize, JsonSchema)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum ColorTheme {
     Light,
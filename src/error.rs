@@ -1,12 +1,13 @@
 use std::{
     fmt::{Debug, Display},
     io,
+    path::PathBuf,
 };
 
 use vdf_parser::error::VdfError;
 use zip::result::ZipError;
 
-use crate::cli::{display_success, error, skip_dialog};
+use crate::cli::{InputPolicy, display_error, display_success, error, skip_dialog};
 
 #[derive(Debug)]
 pub enum Error {
@@ -15,25 +16,48 @@ pub enum Error {
 }
 
 impl Error {
+    /// `None` when the step was skipped, errors only when the policy says to fail
     pub fn error_prone_step<R, F: Fn() -> Result<R>>(
         step: &F,
         success_msg: Option<&str>,
-    ) -> Option<R> {
+    ) -> Result<Option<R>> {
+        Self::error_prone_step_with_policy(step, success_msg, InputPolicy::get())
+    }
+
+    pub fn error_prone_step_with_policy<R, F: Fn() -> Result<R>>(
+        step: &F,
+        success_msg: Option<&str>,
+        policy: InputPolicy,
+    ) -> Result<Option<R>> {
         match step() {
             Ok(res) => {
                 if let Some(success_message) = success_msg {
                     display_success(success_message);
                 }
-                Some(res)
+                Ok(Some(res))
             }
             Err(err) => match err {
-                Error::HandledError(error) => {
-                    skip_dialog(&error.to_string(), error.item(), error.action(), || {
-                        error.handle();
-                        Self::error_prone_step(step, success_msg)
-                    })
-                    .flatten()
-                }
+                Error::HandledError(error) => match policy {
+                    InputPolicy::Ask => {
+                        skip_dialog(&error.to_string(), error.item(), error.action(), || {
+                            error.handle(policy);
+                            Self::error_prone_step_with_policy(step, success_msg, policy)
+                        })
+                        .transpose()
+                        .map(Option::flatten)
+                    }
+                    InputPolicy::Skip => {
+                        display_error(format!("{}, skipping {}", error, error.item()));
+                        Ok(None)
+                    }
+                    InputPolicy::Fix => {
+                        display_error(format!("{}, trying to {}", error, error.action()));
+                        error.handle(policy);
+                        // Only one attempt, a handler that can't fix it would loop forever
+                        Self::error_prone_step_with_policy(step, success_msg, InputPolicy::Skip)
+                    }
+                    InputPolicy::Fail => Err(Error::HandledError(error)),
+                },
                 Error::UnhandledError(e) => panic!("{:?}", e),
             },
         }
@@ -43,7 +67,7 @@ impl Error {
 pub trait HandledError: Display {
     fn item(&self) -> &'static str;
     fn action(&self) -> &'static str;
-    fn handle(&self);
+    fn handle(&self, policy: InputPolicy);
 }
 
 impl<T: HandledError + 'static> From<T> for Error {
@@ -58,6 +82,15 @@ impl Debug for dyn HandledError {
     }
 }
 
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::HandledError(err) => Display::fmt(err, f),
+            Error::UnhandledError(err) => Debug::fmt(err, f),
+        }
+    }
+}
+
 impl From<UnhandledError> for Error {
     fn from(value: UnhandledError) -> Self {
         Error::UnhandledError(value)
@@ -67,11 +100,26 @@ impl From<UnhandledError> for Error {
 #[derive(Debug)]
 pub enum UnhandledError {
     FailedToDownloadTheme,
+    FailedToDownload(String),
+    DataDirNotFound,
+    ThemeNotFound(String),
+    InvalidThemeSource(String),
+    InvalidSpicetifyTheme(String),
+    InvalidSpicetifyConfig(String),
+    InvalidAssetSource(String),
+    EmptyDiaporama,
+    UnsafeBundleEntry(String),
+    InvalidName(String),
+    InvalidWallpaperProperty(String),
+    InvalidAppPath(PathBuf),
+    NoWallpaper,
     SerdeJSON(serde_json::Error),
     VdfError(VdfError),
     IOError(io::Error),
     Reqwest(reqwest::Error),
     Zip(ZipError),
+    #[cfg(windows)]
+    Windows(windows::core::Error),
 }
 
 impl From<io::Error> for Error {
@@ -104,4 +152,11 @@ impl From<ZipError> for Error {
     }
 }
 
+#[cfg(windows)]
+impl From<windows::core::Error> for Error {
+    fn from(value: windows::core::Error) -> Self {
+        Self::UnhandledError(crate::error::UnhandledError::Windows(value))
+    }
+}
+
 pub type Result<T> = std::result::Result<T, Error>;
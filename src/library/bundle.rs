@@ -0,0 +1,118 @@
+use crate::{
+    error::{Result, UnhandledError},
+    library::{Library, cache::Cache},
+    theme::Theme,
+};
+use std::{
+    fs::{self, File},
+    io::{self, Read, Seek},
+    path::{Path, PathBuf},
+};
+use zip::{ZipArchive, ZipWriter, write::SimpleFileOptions};
+
+/// A `.swapeme` archive, the theme manifest next to the assets it references
+pub struct Bundle;
+
+impl Bundle {
+    pub const EXTENSION: &str = ".swapeme";
+    pub const MANIFEST: &str = "theme.swapeme.json";
+
+    pub fn is_bundle(path: &str) -> bool {
+        path.split(['?', '#'])
+            .next()
+            .is_some_and(|path| path.ends_with(Self::EXTENSION))
+    }
+
+    /// Zips `dir` into `output`, `dir` must contain a valid manifest
+    pub fn pack(dir: &Path, output: &Path) -> Result<()> {
+        // Make sure we never share something we won't be able to apply
+        Theme::read_file(dir.join(Self::MANIFEST))?;
+
+        let output_path = output.canonicalize().ok();
+        let mut files = vec![];
+        Self::list_files(dir, &mut files)?;
+
+        let mut zip = ZipWriter::new(File::create(output)?);
+        for file in files {
+            if file.canonicalize().ok() == output_path {
+                continue;
+            }
+            let name = file
+                .strip_prefix(dir)
+                .unwrap()
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            zip.start_file(name, SimpleFileOptions::default())?;
+            io::copy(&mut File::open(file)?, &mut zip)?;
+        }
+        zip.finish()?;
+        Ok(())
+    }
+
+    fn list_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                Self::list_files(&path, files)?;
+            } else {
+                files.push(path);
+            }
+        }
+        Ok(())
+    }
+
+    /// Extracts a bundle into `dest`, refusing entries that would land outside of it
+    pub fn extract(bundle: impl Read + Seek, dest: &Path) -> Result<()> {
+        let mut zip = ZipArchive::new(bundle)?;
+        for i in 0..zip.len() {
+            let mut entry = zip.by_index(i)?;
+            let Some(name) = entry.enclosed_name() else {
+                Err(UnhandledError::UnsafeBundleEntry(entry.name().to_string()))?
+            };
+
+            let path = dest.join(name);
+            if entry.is_dir() {
+                fs::create_dir_all(&path)?;
+                continue;
+            }
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            io::copy(&mut entry, &mut File::create(path)?)?;
+        }
+
+        if !dest.join(Self::MANIFEST).is_file() {
+            Err(UnhandledError::InvalidThemeSource(
+                dest.to_string_lossy().to_string(),
+            ))?
+        }
+        Ok(())
+    }
+}
+
+impl Cache {
+    /// Extracts a bundle once per content, returns the path of its manifest
+    pub fn extract_bundle(&self, bundle: &Path) -> Result<PathBuf> {
+        let content = fs::read(bundle)?;
+        let dir = self.path.join("bundles").join(Self::key(&content));
+        if !dir.join(Bundle::MANIFEST).is_file() {
+            Bundle::extract(io::Cursor::new(content), &dir)?;
+        }
+        Ok(dir.join(Bundle::MANIFEST))
+    }
+}
+
+impl Theme {
+    /// Reads the manifest of a bundle, its assets are extracted into `cache`
+    pub fn read_bundle(path: &Path, cache: &Cache) -> Result<Self> {
+        Theme::read_file(cache.extract_bundle(path)?)
+    }
+}
+
+impl Library {
+    pub fn get_bundle_path(&self, name: &str) -> PathBuf {
+        self.get_themes_path().join(name)
+    }
+}
@@ -0,0 +1,202 @@
+use crate::{
+    cli::display_error,
+    error::{Result, UnhandledError},
+    library::Library,
+};
+use reqwest::{
+    StatusCode,
+    blocking::Client,
+    header::{ETAG, HeaderValue, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use url::Url;
+
+/// Downloaded or copied files swapeme keeps around, like wallpapers
+#[derive(Debug, Clone)]
+pub struct Cache {
+    pub path: PathBuf,
+}
+
+/// What's known about an url downloaded before, in `urls/<url hash>`
+#[derive(Serialize, Deserialize)]
+struct CachedUrl {
+    /// Relative to the cache, under `files/`
+    path: PathBuf,
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+/// Where an asset referenced by a theme comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AssetSource {
+    Remote(String),
+    Local(PathBuf),
+}
+
+impl AssetSource {
+    /// Accepts http(s) urls, `file://` urls, absolute paths and paths relative to `base_dir`
+    pub fn parse(source: &str, base_dir: Option<&Path>) -> Result<Self> {
+        if source.starts_with("http://") || source.starts_with("https://") {
+            return Ok(AssetSource::Remote(source.to_string()));
+        }
+
+        let path = if source.starts_with("file://") {
+            Url::parse(source)
+                .ok()
+                .and_then(|url| url.to_file_path().ok())
+                .ok_or(UnhandledError::InvalidAssetSource(source.to_string()))?
+        } else {
+            PathBuf::from(source)
+        };
+
+        Ok(AssetSource::Local(match base_dir {
+            Some(base_dir) if path.is_relative() => base_dir.join(path),
+            _ => path,
+        }))
+    }
+
+    /// Rewrites a source so it no longer depends on the theme's location
+    pub fn resolve(source: &mut String, base_dir: &Path) -> Result<()> {
+        if let AssetSource::Local(path) = Self::parse(source, Some(base_dir))? {
+            *source = path.to_string_lossy().to_string();
+        }
+        Ok(())
+    }
+}
+
+impl Library {
+    pub fn cache(&self) -> Cache {
+        Cache::new_with_path(self.path.join("cache"))
+    }
+}
+
+impl Cache {
+    pub fn new_with_path(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// SHA-256 of the content, the same across builds so cached paths stay valid
+    pub fn key(content: impl AsRef<[u8]>) -> String {
+        Sha256::digest(content)
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    /// Downloads an url or copies a local file into the cache, returns the cached path
+    pub fn fetch(&self, source: &str) -> Result<PathBuf> {
+        match AssetSource::parse(source, None)? {
+            AssetSource::Local(path) => {
+                if !path.is_file() {
+                    Err(UnhandledError::InvalidAssetSource(source.to_string()))?
+                }
+                // A copy, so themes and bundles can be removed while their wallpapers stay set
+                self.store(&path, fs::read(&path)?)
+            }
+            AssetSource::Remote(url) => self.download(&url),
+        }
+    }
+
+    /// Downloads an url, asking the server whether the copy downloaded before is still current
+    fn download(&self, url: &str) -> Result<PathBuf> {
+        let index = self.path.join("urls").join(Self::key(url));
+        let cached = fs::read_to_string(&index)
+            .ok()
+            .and_then(|content| serde_json::from_str::<CachedUrl>(&content).ok())
+            .filter(|cached| self.path.join(&cached.path).is_file());
+
+        let mut request = Client::new().get(url);
+        if let Some(cached) = &cached {
+            if let Some(etag) = &cached.etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &cached.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+
+        let response = match (request.send(), cached) {
+            (Ok(response), Some(cached)) if response.status() == StatusCode::NOT_MODIFIED => {
+                return Ok(self.path.join(cached.path));
+            }
+            (Ok(response), _) => response,
+            // Offline, the copy downloaded before is the best there is
+            (Err(_), Some(cached)) => {
+                display_error(format!(
+                    "Couldn't reach {}, using the copy downloaded before",
+                    url
+                ));
+                return Ok(self.path.join(cached.path));
+            }
+            (Err(err), None) => Err(err)?,
+        };
+        if !response.status().is_success() {
+            Err(UnhandledError::FailedToDownload(url.to_string()))?
+        }
+
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value: &HeaderValue| value.to_str().ok())
+                .map(str::to_string)
+        };
+        let etag = header(ETAG);
+        let last_modified = header(LAST_MODIFIED);
+        let path = self.store(
+            Path::new(url.split(['?', '#']).next().unwrap_or(url)),
+            response.bytes()?.to_vec(),
+        )?;
+
+        fs::create_dir_all(index.parent().unwrap())?;
+        fs::write(
+            index,
+            serde_json::to_string(&CachedUrl {
+                path: path.strip_prefix(&self.path).unwrap().to_path_buf(),
+                etag,
+                last_modified,
+            })?,
+        )?;
+        Ok(path)
+    }
+
+    /// Writes into `files/<content hash>/`, so a file whose content changed gets a new path
+    fn store(&self, name: &Path, content: Vec<u8>) -> Result<PathBuf> {
+        let file_name = name.file_name().and_then(|name| name.to_str()).ok_or(
+            UnhandledError::InvalidAssetSource(name.to_string_lossy().to_string()),
+        )?;
+
+        let dir = self.path.join("files").join(Self::key(&content));
+        let path = dir.join(file_name);
+        if !path.exists() {
+            fs::create_dir_all(&dir)?;
+            fs::write(&path, content)?;
+        }
+        Ok(path)
+    }
+
+    /// Folder holding exactly these files, fetched in order
+    pub fn fetch_folder(&self, sources: &[String]) -> Result<PathBuf> {
+        let dir = self
+            .path
+            .join("folders")
+            .join(Self::key(sources.join("\n")));
+        if dir.exists() {
+            fs::remove_dir_all(&dir)?;
+        }
+        fs::create_dir_all(&dir)?;
+
+        for (i, source) in sources.iter().enumerate() {
+            let file = self.fetch(source)?;
+            // Prefix with the index so files with the same name don't collide
+            let file_name = format!("{}-{}", i, file.file_name().unwrap().to_string_lossy());
+            fs::copy(file, dir.join(file_name))?;
+        }
+        Ok(dir)
+    }
+}
@@ -0,0 +1,156 @@
+use crate::{
+    error::Result,
+    library::Library,
+    theme::{
+        Theme, ThemeApp, spicetify::ThemeSpicetify, wallpaper_engine::ThemeWallpaperEngine,
+        windows::ThemeWindows,
+    },
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Restore points saved before each apply
+#[derive(Debug, Clone)]
+pub struct History {
+    pub path: PathBuf,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct RestorePoint {
+    /// Unix timestamp in milliseconds, also used as the file name
+    pub created_at: u128,
+    pub theme: Theme,
+}
+
+impl Library {
+    pub fn history(&self) -> History {
+        History::new_with_path(self.path.join("history"))
+    }
+}
+
+impl History {
+    pub const MAX_RESTORE_POINTS: usize = 10;
+
+    pub fn new_with_path(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    fn get_restore_point_path(&self, created_at: u128) -> PathBuf {
+        self.path.join(format!("{}.json", created_at))
+    }
+
+    /// Lists the restore point ids, newest first
+    fn ids(&self) -> Result<Vec<u128>> {
+        if !self.path.exists() {
+            return Ok(vec![]);
+        }
+
+        let mut ids: Vec<u128> = vec![];
+        for entry in fs::read_dir(&self.path)? {
+            let file_name = entry?.file_name();
+            if let Some(id) = file_name
+                .to_str()
+                .and_then(|name| name.strip_suffix(".json"))
+                .and_then(|id| id.parse().ok())
+            {
+                ids.push(id);
+            }
+        }
+        ids.sort_by(|a, b| b.cmp(a));
+        Ok(ids)
+    }
+
+    /// Saves a restore point and drops the oldest ones past [`Self::MAX_RESTORE_POINTS`]
+    pub fn push(&self, theme: Theme) -> Result<u128> {
+        fs::create_dir_all(&self.path)?;
+
+        let mut created_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        // Keep ids unique and increasing even when pushing faster than the clock
+        if let Some(latest) = self.ids()?.first() {
+            created_at = created_at.max(latest + 1);
+        }
+
+        let content = serde_json::to_string_pretty(&RestorePoint { created_at, theme })?;
+        fs::write(self.get_restore_point_path(created_at), content)?;
+
+        for id in self.ids()?.iter().skip(Self::MAX_RESTORE_POINTS) {
+            fs::remove_file(self.get_restore_point_path(*id))?;
+        }
+
+        Ok(created_at)
+    }
+
+    /// Every restore point, newest first
+    pub fn list(&self) -> Result<Vec<RestorePoint>> {
+        self.ids()?.into_iter().map(|id| self.read(id)).collect()
+    }
+
+    pub fn read(&self, id: u128) -> Result<RestorePoint> {
+        let content = fs::read_to_string(self.get_restore_point_path(id))?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    pub fn latest(&self) -> Result<Option<RestorePoint>> {
+        self.ids()?.first().map(|id| self.read(*id)).transpose()
+    }
+
+    pub fn remove(&self, id: u128) -> Result<()> {
+        Ok(fs::remove_file(self.get_restore_point_path(id))?)
+    }
+
+    /// Restores the latest restore point and forgets it, returns false if there was none
+    pub fn undo(&self) -> Result<bool> {
+        let Some(restore_point) = self.latest()? else {
+            return Ok(false);
+        };
+        restore_point.theme.restore()?;
+        self.remove(restore_point.created_at)?;
+        Ok(true)
+    }
+}
+
+impl RestorePoint {
+    /// Names of the apps this restore point would bring back
+    pub fn apps(&self) -> Vec<&'static str> {
+        let mut apps = vec![];
+        if self.theme.wallpaper_engine.is_some() {
+            apps.push(ThemeWallpaperEngine::NAME);
+        }
+        if self.theme.spicetify.is_some() {
+            apps.push(ThemeSpicetify::NAME);
+        }
+        if self.theme.windows.is_some() {
+            apps.push(ThemeWindows::NAME);
+        }
+        apps
+    }
+
+    /// How long ago this restore point was saved, e.g. `5 minutes ago`
+    pub fn age(&self) -> String {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let seconds = now.saturating_sub(self.created_at) / 1000;
+
+        let (amount, unit) = match seconds {
+            0..60 => (seconds, "second"),
+            60..3600 => (seconds / 60, "minute"),
+            3600..86400 => (seconds / 3600, "hour"),
+            _ => (seconds / 86400, "day"),
+        };
+        format!(
+            "{} {}{} ago",
+            amount,
+            unit,
+            if amount == 1 { "" } else { "s" }
+        )
+    }
+}
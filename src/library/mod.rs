@@ -0,0 +1,185 @@
+pub mod bundle;
+pub mod cache;
+pub mod history;
+pub mod settings;
+#[cfg(test)]
+mod test;
+
+use crate::{
+    error::{Result, UnhandledError},
+    library::bundle::Bundle,
+    theme::Theme,
+};
+use reqwest::blocking::get;
+use std::{
+    env,
+    ffi::OsString,
+    fs,
+    io::Cursor,
+    path::{Component, Path, PathBuf},
+};
+
+/// Names from theme files or the command line end up in paths, so only plain file names are allowed
+pub fn check_name(name: &str) -> Result<&str> {
+    let mut components = Path::new(name).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) => Ok(name),
+        _ => Err(UnhandledError::InvalidName(name.to_string()))?,
+    }
+}
+
+/// Per-user directory holding installed themes
+#[derive(Debug, Clone)]
+pub struct Library {
+    pub path: PathBuf,
+}
+
+impl Library {
+    pub const APP_NAME: &str = "swapeme";
+    pub const PATH_ENV_VAR: &str = "SWAPEME_HOME";
+    pub const THEME_EXTENSION: &str = ".swapeme.json";
+
+    pub fn new_with_path(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// Uses `SWAPEME_HOME` if set, otherwise the user's data dir
+    pub fn new() -> Result<Self> {
+        Self::new_with_home(env::var_os(Self::PATH_ENV_VAR))
+    }
+
+    /// Uses `home`, the value of `SWAPEME_HOME`, if set, otherwise the user's data dir
+    pub fn new_with_home(home: Option<OsString>) -> Result<Self> {
+        let path = match home {
+            Some(path) => PathBuf::from(path),
+            None => dirs::data_dir()
+                .ok_or(UnhandledError::DataDirNotFound)?
+                .join(Self::APP_NAME),
+        };
+        Ok(Self::new_with_path(path))
+    }
+
+    pub fn get_themes_path(&self) -> PathBuf {
+        self.path.join("themes")
+    }
+
+    pub fn get_theme_path(&self, name: &str) -> PathBuf {
+        self.get_themes_path()
+            .join(format!("{}{}", name, Self::THEME_EXTENSION))
+    }
+
+    pub fn read_theme(&self, name: &str) -> Result<Theme> {
+        check_name(name)?;
+        let bundle = self.get_bundle_path(name).join(Bundle::MANIFEST);
+        if bundle.is_file() {
+            return Theme::read_file(bundle);
+        }
+
+        let path = self.get_theme_path(name);
+        if !path.exists() {
+            Err(UnhandledError::ThemeNotFound(name.to_string()))?
+        }
+        Theme::read_file(path)
+    }
+
+    pub fn list(&self) -> Result<Vec<String>> {
+        let path = self.get_themes_path();
+        if !path.exists() {
+            return Ok(vec![]);
+        }
+
+        let mut themes = vec![];
+        for entry in fs::read_dir(path)? {
+            let entry = entry?;
+            let file_name = entry.file_name();
+            let Some(file_name) = file_name.to_str() else {
+                continue;
+            };
+
+            if let Some(name) = file_name.strip_suffix(Self::THEME_EXTENSION) {
+                themes.push(name.to_string());
+            } else if entry.path().join(Bundle::MANIFEST).is_file() {
+                themes.push(file_name.to_string());
+            }
+        }
+        themes.sort();
+        Ok(themes)
+    }
+
+    /// Installs a theme or a bundle from a local file or an http(s) url and returns its name
+    pub fn install(&self, source: &str) -> Result<String> {
+        let content = if source.starts_with("http://") || source.starts_with("https://") {
+            let response = get(source)?;
+            if !response.status().is_success() {
+                Err(UnhandledError::FailedToDownloadTheme)?
+            }
+            response.bytes()?.to_vec()
+        } else {
+            fs::read(source)?
+        };
+
+        let name = Self::theme_name_from_source(source)
+            .ok_or(UnhandledError::InvalidThemeSource(source.to_string()))?;
+        fs::create_dir_all(self.get_themes_path())?;
+
+        if Bundle::is_bundle(source) {
+            self.install_bundle(&name, content)?;
+        } else {
+            // Make sure we never install something we won't be able to apply
+            Theme::parse_json(&String::from_utf8_lossy(&content))?;
+            fs::write(self.get_theme_path(&name), content)?;
+        }
+        Ok(name)
+    }
+
+    /// Extracts next to the installed themes first, so a broken bundle never replaces a working one
+    fn install_bundle(&self, name: &str, content: Vec<u8>) -> Result<()> {
+        let temp = self.get_themes_path().join(format!(".{}.tmp", name));
+        if temp.exists() {
+            fs::remove_dir_all(&temp)?;
+        }
+
+        let extracted = Bundle::extract(Cursor::new(content), &temp)
+            .and_then(|_| Theme::read_file(temp.join(Bundle::MANIFEST)));
+        if let Err(err) = extracted {
+            fs::remove_dir_all(&temp)?;
+            return Err(err);
+        }
+
+        let path = self.get_bundle_path(name);
+        if path.exists() {
+            fs::remove_dir_all(&path)?;
+        }
+        Ok(fs::rename(temp, path)?)
+    }
+
+    pub fn remove(&self, name: &str) -> Result<()> {
+        check_name(name)?;
+        let bundle = self.get_bundle_path(name);
+        if bundle.join(Bundle::MANIFEST).is_file() {
+            return Ok(fs::remove_dir_all(bundle)?);
+        }
+
+        let path = self.get_theme_path(name);
+        if !path.exists() {
+            Err(UnhandledError::ThemeNotFound(name.to_string()))?
+        }
+        Ok(fs::remove_file(path)?)
+    }
+
+    fn theme_name_from_source(source: &str) -> Option<String> {
+        let source = source.split(['?', '#']).next()?;
+        let file_name = Path::new(source.trim_end_matches('/'))
+            .file_name()?
+            .to_str()?
+            .to_string();
+
+        let name = file_name
+            .strip_suffix(Self::THEME_EXTENSION)
+            .or(file_name.strip_suffix(Bundle::EXTENSION))
+            .or(file_name.strip_suffix(".json"))
+            .unwrap_or(&file_name);
+
+        (!name.is_empty()).then(|| name.to_string())
+    }
+}
@@ -0,0 +1,54 @@
+use crate::{error::Result, library::Library};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::PathBuf};
+
+/// User preferences kept next to the installed themes
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct Settings {
+    #[serde(default)]
+    pub paths: SettingsPaths,
+}
+
+/// Installations to use instead of detecting them
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct SettingsPaths {
+    /// Folder containing `steamapps`
+    pub steam: Option<PathBuf>,
+    /// Folder containing `wallpaper32.exe`
+    pub wallpaper_engine: Option<PathBuf>,
+    /// Folder containing the spicetify executable
+    pub spicetify: Option<PathBuf>,
+    /// The linux-wallpaperengine executable
+    pub linux_wallpaperengine: Option<PathBuf>,
+}
+
+impl Library {
+    pub fn get_settings_path(&self) -> PathBuf {
+        self.path.join("settings.json")
+    }
+
+    pub fn read_settings(&self) -> Result<Settings> {
+        let path = self.get_settings_path();
+        if !path.exists() {
+            return Ok(Settings::default());
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn write_settings(&self, settings: &Settings) -> Result<()> {
+        fs::create_dir_all(&self.path)?;
+        Ok(fs::write(
+            self.get_settings_path(),
+            serde_json::to_string_pretty(settings)?,
+        )?)
+    }
+}
+
+impl Settings {
+    /// The user's settings, defaults if they can't be read
+    pub fn load() -> Self {
+        Library::new()
+            .and_then(|library| library.read_settings())
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,313 @@
+use crate::{
+    error::{Error, UnhandledError},
+    library::{
+        Library,
+        bundle::Bundle,
+        cache::{AssetSource, Cache},
+        history::History,
+    },
+    test::{serve_with_headers, temp_dir},
+    theme::{Theme, windows::wallpaper::WindowsWallpaper},
+};
+use std::{
+    fs,
+    io::{Cursor, Write},
+    path::Path,
+};
+use zip::{ZipWriter, write::SimpleFileOptions};
+
+#[test]
+fn test_install_list_remove() {
+    let library = Library::new_with_path(temp_dir("library"));
+    assert!(library.list().unwrap().is_empty());
+
+    let name = library.install("resources/test/cat.swapeme.json").unwrap();
+    assert_eq!(name, "cat");
+    library
+        .install("resources/test/stars.swapeme.json")
+        .unwrap();
+    assert_eq!(library.list().unwrap(), vec!["cat", "stars"]);
+    assert!(library.read_theme("cat").unwrap().spicetify.is_some());
+
+    library.remove("cat").unwrap();
+    assert_eq!(library.list().unwrap(), vec!["stars"]);
+    assert!(library.remove("cat").is_err());
+    assert!(library.read_theme("cat").is_err());
+
+    // Names can't point outside of the themes folder
+    fs::write(library.path.join("settings.swapeme.json"), "{}").unwrap();
+    for name in ["../settings", "themes/../../settings", "/tmp/settings"] {
+        assert!(matches!(
+            library.remove(name),
+            Err(Error::UnhandledError(UnhandledError::InvalidName(_)))
+        ));
+        assert!(library.read_theme(name).is_err());
+    }
+    assert!(library.path.join("settings.swapeme.json").exists());
+}
+
+#[test]
+fn test_install_rejects_invalid_theme() {
+    let dir = temp_dir("library_invalid");
+    let source = dir.join("broken.swapeme.json");
+    fs::write(&source, "{ \"spicetify\": 42 }").unwrap();
+
+    let library = Library::new_with_path(dir.join("lib"));
+    assert!(library.install(source.to_str().unwrap()).is_err());
+    assert!(library.list().unwrap().is_empty());
+}
+
+#[test]
+fn test_theme_name_from_source() {
+    for (source, name) in [
+        ("themes/cat.swapeme.json", "cat"),
+        ("https://example.com/dark.json?raw=true", "dark"),
+        ("https://example.com/themes/stars.swapeme.json", "stars"),
+    ] {
+        assert_eq!(Library::theme_name_from_source(source).unwrap(), name);
+    }
+}
+
+#[test]
+fn test_library_path_env_var() {
+    let dir = temp_dir("library_env");
+    assert_eq!(
+        Library::new_with_home(Some(dir.clone().into()))
+            .unwrap()
+            .path,
+        dir
+    );
+    if let Ok(library) = Library::new_with_home(None) {
+        assert!(library.path.ends_with(Library::APP_NAME));
+    }
+}
+
+#[test]
+fn test_history_is_bounded() {
+    let history = Library::new_with_path(temp_dir("history")).history();
+    assert!(history.latest().unwrap().is_none());
+
+    let mut ids = vec![];
+    for i in 0..History::MAX_RESTORE_POINTS + 2 {
+        let mut theme = Theme::read_file("resources/test/cat.swapeme.json").unwrap();
+        theme.version = Some(i.to_string());
+        ids.push(history.push(theme).unwrap());
+    }
+
+    let restore_points = history.list().unwrap();
+    assert_eq!(restore_points.len(), History::MAX_RESTORE_POINTS);
+    assert_eq!(
+        restore_points
+            .iter()
+            .map(|r| r.created_at)
+            .collect::<Vec<_>>(),
+        ids.iter()
+            .rev()
+            .take(History::MAX_RESTORE_POINTS)
+            .copied()
+            .collect::<Vec<_>>()
+    );
+
+    let latest = history.latest().unwrap().unwrap();
+    assert_eq!(
+        latest.theme.version,
+        Some((History::MAX_RESTORE_POINTS + 1).to_string())
+    );
+    assert_eq!(
+        latest.apps(),
+        vec!["Wallpaper engine", "Spotify (Spicetify)"]
+    );
+
+    history.remove(latest.created_at).unwrap();
+    assert_eq!(
+        history.list().unwrap().len(),
+        History::MAX_RESTORE_POINTS - 1
+    );
+}
+
+#[test]
+fn test_asset_sources() {
+    let base_dir = Path::new("themes").join("stars");
+    assert_eq!(
+        AssetSource::parse("https://example.com/stars.png", Some(&base_dir)).unwrap(),
+        AssetSource::Remote("https://example.com/stars.png".to_string())
+    );
+    assert_eq!(
+        AssetSource::parse("images/stars.png", Some(&base_dir)).unwrap(),
+        AssetSource::Local(base_dir.join("images/stars.png"))
+    );
+    assert!(AssetSource::parse("file://host:bad/", None).is_err());
+}
+
+#[test]
+// Absolute paths and file urls look different on windows
+#[cfg(unix)]
+fn test_absolute_asset_sources() {
+    let base_dir = Path::new("/themes/stars");
+    for (source, expected) in [
+        (
+            "file:///wallpapers/my%20stars.png",
+            "/wallpapers/my stars.png",
+        ),
+        ("/wallpapers/stars.png", "/wallpapers/stars.png"),
+    ] {
+        assert_eq!(
+            AssetSource::parse(source, Some(base_dir)).unwrap(),
+            AssetSource::Local(expected.into())
+        );
+    }
+}
+
+#[test]
+fn test_fetch_missing_local_file() {
+    let cache = Cache::new_with_path(temp_dir("cache_missing"));
+    assert!(cache.fetch("/does/not/exist.png").is_err());
+}
+
+/// A theme folder with a wallpaper next to its manifest
+fn theme_folder(dir: &Path) {
+    fs::create_dir_all(dir.join("images")).unwrap();
+    fs::write(dir.join("images").join("stars.png"), "stars").unwrap();
+    fs::write(
+        dir.join(Bundle::MANIFEST),
+        r#"{ "windows": { "wallpaper": { "type": "single", "url": "images/stars.png" } } }"#,
+    )
+    .unwrap();
+}
+
+fn wallpaper_url(theme: Theme) -> String {
+    match theme.windows.unwrap().wallpaper.unwrap() {
+        WindowsWallpaper::Single { url, .. } => url,
+        other => panic!("unexpected wallpaper {:?}", other),
+    }
+}
+
+#[test]
+fn test_pack_and_install_bundle() {
+    let dir = temp_dir("bundle");
+    theme_folder(&dir.join("stars"));
+    let bundle = dir.join("stars.swapeme");
+    Bundle::pack(&dir.join("stars"), &bundle).unwrap();
+
+    let library = Library::new_with_path(dir.join("lib"));
+    assert_eq!(library.install(bundle.to_str().unwrap()).unwrap(), "stars");
+    assert_eq!(library.list().unwrap(), vec!["stars"]);
+
+    // Relative assets point inside the installed bundle
+    let url = wallpaper_url(library.read_theme("stars").unwrap());
+    assert!(
+        url.starts_with(
+            fs::canonicalize(library.get_bundle_path("stars"))
+                .unwrap()
+                .to_str()
+                .unwrap()
+        )
+    );
+    assert_eq!(fs::read_to_string(url).unwrap(), "stars");
+
+    library.remove("stars").unwrap();
+    assert!(library.list().unwrap().is_empty());
+}
+
+#[test]
+fn test_read_bundle() {
+    let dir = temp_dir("bundle_read");
+    theme_folder(&dir.join("stars"));
+    let bundle = dir.join("stars.swapeme");
+    Bundle::pack(&dir.join("stars"), &bundle).unwrap();
+
+    let cache = Cache::new_with_path(dir.join("cache"));
+    let url = wallpaper_url(Theme::read_bundle(&bundle, &cache).unwrap());
+    assert_eq!(fs::read_to_string(url).unwrap(), "stars");
+}
+
+#[test]
+fn test_pack_requires_manifest() {
+    let dir = temp_dir("bundle_no_manifest");
+    assert!(Bundle::pack(&dir, &dir.join("empty.swapeme")).is_err());
+}
+
+#[test]
+fn test_extract_rejects_zip_slip() {
+    let dir = temp_dir("bundle_zip_slip");
+    let mut zip = ZipWriter::new(Cursor::new(vec![]));
+    zip.start_file(Bundle::MANIFEST, SimpleFileOptions::default())
+        .unwrap();
+    zip.write_all(b"{}").unwrap();
+    zip.start_file("../evil.txt", SimpleFileOptions::default())
+        .unwrap();
+    zip.write_all(b"evil").unwrap();
+    let content = zip.finish().unwrap().into_inner();
+
+    let bundle = dir.join("evil.swapeme");
+    fs::write(&bundle, content).unwrap();
+    let library = Library::new_with_path(dir.join("lib"));
+    assert!(library.install(bundle.to_str().unwrap()).is_err());
+    assert!(!dir.join("lib").join("evil.txt").exists());
+    assert!(!dir.join("lib").join("themes").join("evil.txt").exists());
+    assert!(library.list().unwrap().is_empty());
+}
+
+#[test]
+fn test_settings_round_trip() {
+    let library = Library::new_with_path(temp_dir("settings"));
+    let mut settings = library.read_settings().unwrap();
+    assert!(settings.paths.steam.is_none());
+
+    settings.paths.spicetify = Some("C:\\spicetify".into());
+    library.write_settings(&settings).unwrap();
+    assert_eq!(
+        library.read_settings().unwrap().paths.spicetify,
+        Some("C:\\spicetify".into())
+    );
+}
+
+#[test]
+fn test_cache_fetch() {
+    let dir = temp_dir("cache_fetch");
+    let cache = Cache::new_with_path(dir.join("cache"));
+    assert_eq!(
+        Cache::key("abc"),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+
+    // Local files are copied, a new content gets a new path
+    let image = dir.join("stars.png");
+    fs::write(&image, "stars").unwrap();
+    let cached = cache.fetch(&image.to_string_lossy()).unwrap();
+    assert!(cached.starts_with(&cache.path));
+    fs::write(&image, "more stars").unwrap();
+    let updated = cache.fetch(&image.to_string_lossy()).unwrap();
+    assert_ne!(cached, updated);
+    assert_eq!(fs::read_to_string(cached).unwrap(), "stars");
+
+    // Urls are revalidated, the server decides whether the copy downloaded before is current
+    let (server, requests) = serve_with_headers(vec![
+        (200, vec![("ETag", "\"v1\"".to_string())], b"moon".to_vec()),
+        (304, vec![], vec![]),
+        (
+            200,
+            vec![("ETag", "\"v2\"".to_string())],
+            b"full moon".to_vec(),
+        ),
+    ]);
+    let url = format!("{}/moon.png?size=large", server);
+    let downloaded = cache.fetch(&url).unwrap();
+    assert!(downloaded.ends_with("moon.png"));
+    assert_eq!(fs::read_to_string(&downloaded).unwrap(), "moon");
+
+    assert_eq!(cache.fetch(&url).unwrap(), downloaded);
+    let revalidation = requests.iter().nth(1).unwrap();
+    assert!(
+        revalidation
+            .iter()
+            .any(|line| line.eq_ignore_ascii_case("if-none-match: \"v1\""))
+    );
+
+    let updated = cache.fetch(&url).unwrap();
+    assert_ne!(updated, downloaded);
+    assert_eq!(fs::read_to_string(&updated).unwrap(), "full moon");
+
+    // Once the server is gone, the last download is used
+    assert_eq!(cache.fetch(&url).unwrap(), updated);
+}
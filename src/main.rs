@@ -4,15 +4,26 @@ mod test;
 
 pub mod cli;
 pub mod color;
+pub mod library;
 pub mod theme;
 
 use crate::{
-    cli::error,
-    theme::{Theme, ThemeApp, spicetify::ThemeSpicetify, wallpaper_engine::ThemeWallpaperEngine},
+    cli::{InputPolicy, display_error, display_success, error},
+    error::Result,
+    library::{Library, bundle::Bundle},
+    theme::{
+        Theme, ThemeApp,
+        spicetify::ThemeSpicetify,
+        wallpaper_engine::{
+            ThemeWallpaperEngine, WallpaperEngine, WallpaperKind, control::WallpaperCommand,
+            workshop::human_size,
+        },
+        windows::ThemeWindows,
+    },
 };
-use clap::{Arg, Command};
+use clap::{Arg, ArgAction, Command};
 use colored::Colorize;
-use std::{fs, path::Path};
+use std::{fs, path::PathBuf, process};
 
 fn cli() -> Command {
     Command::new("swapeme")
@@ -20,19 +31,109 @@ fn cli() -> Command {
         .subcommand_required(true)
         .arg_required_else_help(true)
         .allow_external_subcommands(false)
+        .arg(
+            Arg::new("yes")
+                .long("yes")
+                .short('y')
+                .global(true)
+                .action(ArgAction::SetTrue)
+                .help("Never prompt, try to fix errors and answer yes to questions"),
+        )
+        .arg(
+            Arg::new("no-input")
+                .long("no-input")
+                .global(true)
+                .action(ArgAction::SetTrue)
+                .help("Never prompt, resolve errors according to --on-error"),
+        )
+        .arg(
+            Arg::new("on-error")
+                .long("on-error")
+                .global(true)
+                .value_parser(InputPolicy::VALUES)
+                .help("What to do with errors when not prompting [default: skip]"),
+        )
         .subcommand(Command::new("gen_schema").about("Generates the theme json schema"))
         .subcommand(
             Command::new("apply")
                 .about("Applies a theme")
                 .arg(Arg::new("theme"))
+                .arg(
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .action(ArgAction::SetTrue)
+                        .help("Prints what would change without applying anything"),
+                )
                 .arg_required_else_help(true),
         )
         .subcommand(Command::new("create").about("Create a new theme based on your current config"))
+        .subcommand(Command::new("undo").about("Restores what was there before the last apply"))
+        .subcommand(Command::new("history").about("Lists the saved restore points"))
+        .subcommand(Command::new("path").about("Prints where installed themes are stored"))
+        .subcommand(Command::new("list").about("Lists installed themes"))
+        .subcommand(
+            Command::new("install")
+                .about("Installs a theme or a .swapeme bundle from a file or an url")
+                .arg(Arg::new("source"))
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            Command::new("pack")
+                .about("Packs a theme folder and its assets into a .swapeme bundle")
+                .arg(Arg::new("dir").help("Folder containing a theme.swapeme.json"))
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .short('o')
+                        .help("Where to write the bundle [default: <dir>.swapeme]"),
+                )
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            Command::new("we")
+                .about("Wallpaper engine commands")
+                .subcommand_required(true)
+                .arg_required_else_help(true)
+                .subcommand(Command::new("list").about("Lists installed wallpapers"))
+                .subcommand(Command::new("play").about("Resumes wallpapers"))
+                .subcommand(Command::new("pause").about("Pauses wallpapers"))
+                .subcommand(Command::new("stop").about("Stops wallpapers, freeing their memory"))
+                .subcommand(Command::new("mute").about("Mutes wallpapers"))
+                .subcommand(Command::new("unmute").about("Unmutes wallpapers"))
+                .subcommand(Command::new("hide-icons").about("Hides desktop icons"))
+                .subcommand(Command::new("show-icons").about("Shows desktop icons"))
+                .subcommand(
+                    Command::new("close")
+                        .about("Closes the wallpaper of a monitor, or of every monitor")
+                        .arg(monitor_arg()),
+                )
+                .subcommand(
+                    Command::new("properties")
+                        .about("Changes properties of the wallpaper of a monitor")
+                        .arg(Arg::new("properties").help(r#"Json object, e.g. {"speed": 2}"#))
+                        .arg(monitor_arg())
+                        .arg_required_else_help(true),
+                ),
+        )
+        .subcommand(
+            Command::new("remove")
+                .about("Removes an installed theme")
+                .arg(Arg::new("theme"))
+                .arg_required_else_help(true),
+        )
 }
 
 fn main() {
     let matches = cli().get_matches();
 
+    if let Some(policy) = matches.get_one::<String>("on-error") {
+        InputPolicy::from_name(policy).unwrap().set();
+    } else if matches.get_flag("yes") {
+        InputPolicy::Fix.set();
+    } else if matches.get_flag("no-input") {
+        InputPolicy::Skip.set();
+    }
+
     match matches.subcommand() {
         Some(("gen_schema", _)) => {
             let schema = schemars::schema_for!(Theme);
@@ -43,22 +144,190 @@ fn main() {
             let theme = Theme {
                 author: None,
                 version: None,
-                spicetify: ThemeSpicetify::ask_to_get_current(),
-                windows: None,
-                wallpaper_engine: ThemeWallpaperEngine::ask_to_get_current(),
+                spicetify: or_exit(ThemeSpicetify::ask_to_get_current()),
+                windows: or_exit(ThemeWindows::ask_to_get_current()),
+                wallpaper_engine: or_exit(ThemeWallpaperEngine::ask_to_get_current()),
+                base_dir: None,
             };
-            theme.write_json("test.swapeme.json").unwrap()
+            theme.write_json("test.swapeme.json").unwrap();
+
+            display_success("Created theme test.swapeme.json");
+            if let (Some(wallpaper_engine), Ok(app)) =
+                (&theme.wallpaper_engine, WallpaperEngine::new())
+            {
+                for line in wallpaper_engine.describe(&app) {
+                    println!("  {}", line);
+                }
+            }
         }
         Some(("apply", arg_matches)) => {
             let theme = arg_matches.get_one::<String>("theme").unwrap();
-            Theme::read_file(Path::new("resources/test").join(format!("{}.swapeme.json", theme)))
-                .expect(&error(format!(
-                    "Failed to read theme, make sure it's installed and valid, run {} to know where to place an installed theme", "swapeme path".bold(),
+            let theme = Theme::read_file(theme).unwrap_or_else(|_| {
+                panic!(
+                    "{}",
+                    error(format!(
+                        "Failed to read theme, make sure it's installed and valid, run {} to know where to place an installed theme",
+                        "swapeme path".bold(),
+                    ))
                 )
-                ))
-                .apply()
-                .unwrap();
+            });
+
+            if arg_matches.get_flag("dry-run") {
+                print!("{}", or_exit(theme.plan()));
+            } else {
+                or_exit(theme.apply());
+            }
+        }
+        Some(("undo", _)) => {
+            if library().history().undo().unwrap() {
+                display_success("Restored previous theme");
+            } else {
+                display_error("Nothing to undo");
+            }
+        }
+        Some(("history", _)) => {
+            for restore_point in library().history().list().unwrap() {
+                println!(
+                    "{} {}",
+                    restore_point.age().bold(),
+                    restore_point.apps().join(", ")
+                );
+            }
+        }
+        Some(("path", _)) => {
+            println!("{}", library().get_themes_path().display());
+        }
+        Some(("list", _)) => {
+            for theme in library().list().unwrap() {
+                println!("{}", theme);
+            }
+        }
+        Some(("install", arg_matches)) => {
+            let source = arg_matches.get_one::<String>("source").unwrap();
+            let name = library().install(source).unwrap_or_else(|_| {
+                panic!(
+                    "{}",
+                    error(format!("Failed to install theme from {}", source))
+                )
+            });
+            display_success(format!("Installed theme {}", name));
+        }
+        Some(("pack", arg_matches)) => {
+            let dir = PathBuf::from(arg_matches.get_one::<String>("dir").unwrap());
+            let output = match arg_matches.get_one::<String>("output") {
+                Some(output) => PathBuf::from(output),
+                None => {
+                    let name = fs::canonicalize(&dir)
+                        .ok()
+                        .and_then(|dir| Some(dir.file_name()?.to_string_lossy().to_string()))
+                        .unwrap_or("theme".to_string());
+                    PathBuf::from(format!("{}{}", name, Bundle::EXTENSION))
+                }
+            };
+            Bundle::pack(&dir, &output).unwrap_or_else(|_| {
+                panic!(
+                    "{}",
+                    error(format!(
+                        "Failed to pack {}, make sure it contains a valid {}",
+                        dir.display(),
+                        Bundle::MANIFEST
+                    ))
+                )
+            });
+            display_success(format!("Packed theme into {}", output.display()));
+        }
+        Some(("we", arg_matches)) => match arg_matches.subcommand() {
+            Some(("list", _)) => {
+                let app = wallpaper_engine();
+                // Only steam knows the size of workshop wallpapers
+                let manifest = app.read_workshop_manifest().unwrap_or_default();
+                for (wallpaper, project) in app.list_wallpapers().unwrap() {
+                    let size = match wallpaper.kind {
+                        WallpaperKind::Workshop => {
+                            manifest.get(&wallpaper.id).and_then(|item| item.size)
+                        }
+                        _ => None,
+                    };
+                    println!(
+                        "{} {} {} {}{}{}",
+                        wallpaper.id,
+                        project.title.unwrap_or_default().bold(),
+                        project.kind,
+                        wallpaper.kind,
+                        size.map(|size| format!(" {}", human_size(size)))
+                            .unwrap_or_default(),
+                        if project.tags.is_empty() {
+                            String::new()
+                        } else {
+                            format!(" [{}]", project.tags.join(", "))
+                        }
+                    );
+                }
+            }
+            Some((name, command_matches)) => {
+                let monitor = command_matches
+                    .try_get_one::<u32>("monitor")
+                    .ok()
+                    .flatten()
+                    .copied();
+                let command = match name {
+                    "play" => WallpaperCommand::Play,
+                    "pause" => WallpaperCommand::Pause,
+                    "stop" => WallpaperCommand::Stop,
+                    "mute" => WallpaperCommand::Mute,
+                    "unmute" => WallpaperCommand::Unmute,
+                    "hide-icons" => WallpaperCommand::HideIcons,
+                    "show-icons" => WallpaperCommand::ShowIcons,
+                    "close" => WallpaperCommand::CloseWallpaper { monitor },
+                    "properties" => {
+                        let properties = command_matches.get_one::<String>("properties").unwrap();
+                        WallpaperCommand::ApplyProperties {
+                            properties: serde_json::from_str(properties).unwrap_or_else(|_| {
+                                panic!("{}", error("Properties must be a json object"))
+                            }),
+                            monitor,
+                        }
+                    }
+                    _ => unreachable!(),
+                };
+                wallpaper_engine().control(&command).unwrap_or_else(|_| {
+                    panic!("{}", error(format!("Wallpaper engine failed to {}", name)))
+                });
+            }
+            _ => unreachable!(),
+        },
+        Some(("remove", arg_matches)) => {
+            let theme = arg_matches.get_one::<String>("theme").unwrap();
+            library().remove(theme).unwrap_or_else(|_| {
+                panic!("{}", error(format!("Theme {} is not installed", theme)))
+            });
+            display_success(format!("Removed theme {}", theme));
         }
         _ => unreachable!(),
     }
 }
+
+fn monitor_arg() -> Arg {
+    Arg::new("monitor")
+        .long("monitor")
+        .short('m')
+        .value_parser(clap::value_parser!(u32))
+        .help("Index of the monitor [default: every monitor]")
+}
+
+/// Exits with an error code instead of panicking, e.g. on the first error with `--on-error fail`
+fn or_exit<T>(result: Result<T>) -> T {
+    result.unwrap_or_else(|err| {
+        display_error(err);
+        process::exit(1)
+    })
+}
+
+fn wallpaper_engine() -> WallpaperEngine {
+    WallpaperEngine::new()
+        .unwrap_or_else(|_| panic!("{}", error("Couldn't find wallpaper engine's installation")))
+}
+
+fn library() -> Library {
+    Library::new().unwrap_or_else(|_| panic!("{}", error("Couldn't find where to store themes")))
+}
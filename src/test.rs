@@ -1,9 +1,160 @@
-use crate::theme::Theme;
+use crate::{
+    cli::InputPolicy,
+    error::{Error, HandledError, Result},
+    library::Library,
+    theme::Theme,
+};
+use std::{
+    cell::Cell,
+    env,
+    fmt::Display,
+    fs,
+    io::{BufRead, BufReader, Write},
+    net::TcpListener,
+    path::PathBuf,
+    rc::Rc,
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+/// Creates an empty directory unique to the calling test
+pub fn temp_dir(name: &str) -> PathBuf {
+    let path = env::temp_dir().join("swapeme-test").join(name);
+    if path.exists() {
+        fs::remove_dir_all(&path).unwrap();
+    }
+    fs::create_dir_all(&path).unwrap();
+    path
+}
+
+/// Answers requests in order with a status and a body, standing in for a web server
+pub fn serve(responses: Vec<(u16, Vec<u8>)>) -> String {
+    serve_with_headers(
+        responses
+            .into_iter()
+            .map(|(status, body)| (status, vec![], body))
+            .collect(),
+    )
+    .0
+}
+
+/// Status, headers and body of an answer from [`serve_with_headers`]
+pub type Response = (u16, Vec<(&'static str, String)>, Vec<u8>);
+
+/// Like [`serve`] with response headers, also sends back the header lines of every request
+pub fn serve_with_headers(responses: Vec<Response>) -> (String, Receiver<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let (sender, requests) = mpsc::channel();
+    thread::spawn(move || {
+        for (status, headers, body) in responses {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(&stream);
+            let mut request = vec![];
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                request.push(line.trim_end().to_string());
+                line.clear();
+            }
+            // The test may not care about the requests and have dropped the receiver
+            let _ = sender.send(request);
+
+            write!(stream, "HTTP/1.1 {} Test\r\n", status).unwrap();
+            for (name, value) in headers {
+                write!(stream, "{}: {}\r\n", name, value).unwrap();
+            }
+            write!(
+                stream,
+                "Content-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            )
+            .unwrap();
+            stream.write_all(&body).unwrap();
+        }
+    });
+    (url, requests)
+}
 
 #[test]
 fn test_theme() {
+    InputPolicy::Skip.set_for_thread();
+    let library = Library::new_with_path(temp_dir("theme"));
     Theme::read_file("resources/test/test.swapeme.json")
         .unwrap()
-        .apply()
+        .apply_with_library(&library)
         .unwrap();
 }
+
+struct FakeError(Rc<Cell<u32>>);
+
+impl Display for FakeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Fake error")
+    }
+}
+
+impl HandledError for FakeError {
+    fn item(&self) -> &'static str {
+        "fake step"
+    }
+    fn action(&self) -> &'static str {
+        "fix it"
+    }
+    fn handle(&self, policy: InputPolicy) {
+        assert!(!policy.is_interactive());
+        self.0.set(self.0.get() + 1);
+    }
+}
+
+/// A step that fails until it has been handled `fixes_needed` times
+fn failing_step(handled: &Rc<Cell<u32>>, fixes_needed: u32) -> impl Fn() -> Result<u32> {
+    move || {
+        if handled.get() >= fixes_needed {
+            Ok(handled.get())
+        } else {
+            Err(FakeError(handled.clone()).into())
+        }
+    }
+}
+
+#[test]
+fn test_skip_policy() {
+    let handled = Rc::new(Cell::new(0));
+    let step = failing_step(&handled, 1);
+    assert_eq!(
+        Error::error_prone_step_with_policy(&step, None, InputPolicy::Skip).unwrap(),
+        None
+    );
+    assert_eq!(handled.get(), 0);
+}
+
+#[test]
+fn test_fix_policy() {
+    let handled = Rc::new(Cell::new(0));
+    let step = failing_step(&handled, 1);
+    assert_eq!(
+        Error::error_prone_step_with_policy(&step, None, InputPolicy::Fix).unwrap(),
+        Some(1)
+    );
+
+    // Gives up after one attempt instead of looping
+    let handled = Rc::new(Cell::new(0));
+    let step = failing_step(&handled, 2);
+    assert_eq!(
+        Error::error_prone_step_with_policy(&step, None, InputPolicy::Fix).unwrap(),
+        None
+    );
+    assert_eq!(handled.get(), 1);
+}
+
+#[test]
+fn test_fail_policy() {
+    let handled = Rc::new(Cell::new(0));
+    let step = failing_step(&handled, 1);
+    // Errors out instead of panicking, so the process exits with an error code
+    assert!(matches!(
+        Error::error_prone_step_with_policy(&step, None, InputPolicy::Fail),
+        Err(Error::HandledError(_))
+    ));
+    assert_eq!(handled.get(), 0);
+}
@@ -1,10 +1,17 @@
+pub mod monitor;
+pub mod plan;
 pub mod spicetify;
+#[cfg(test)]
+mod test;
 pub mod wallpaper_engine;
 pub mod windows;
 
 use crate::{
+    cli::{InputPolicy, display_error},
     error::Result,
+    library::{Library, bundle::Bundle},
     theme::{
+        plan::{Plan, PlanSection, PlannedAction},
         spicetify::ThemeSpicetify,
         wallpaper_engine::{ThemeAuthor, ThemeWallpaperEngine},
         windows::ThemeWindows,
@@ -13,7 +20,10 @@ use crate::{
 use dialoguer::Confirm;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::{fs, path::Path};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
 
 #[derive(Deserialize, Serialize, JsonSchema)]
 pub struct Theme {
@@ -22,35 +32,81 @@ pub struct Theme {
     pub wallpaper_engine: Option<ThemeWallpaperEngine>,
     pub spicetify: Option<ThemeSpicetify>,
     pub windows: Option<ThemeWindows>,
+    /// Directory of the theme file, relative assets are resolved against it
+    #[serde(skip)]
+    pub base_dir: Option<PathBuf>,
 }
 
 pub trait ThemeApp {
     const NAME: &'static str;
-    type App;
+    type App: Clone;
 
-    fn get_app() -> Option<Self::App>;
+    /// `None` when the app's missing and the user skipped it
+    fn get_app() -> Result<Option<Self::App>>;
     fn apply(&self, app: Self::App) -> Result<()>;
+    /// Lists what [`ThemeApp::apply`] would do without changing anything
+    fn plan(&self, app: &Self::App) -> Result<Vec<PlannedAction>>;
     fn get_current(app: Self::App) -> Result<Self>
     where
         Self: Sized;
 
-    fn ask_to_get_current() -> Option<Self>
+    fn ask_to_get_current() -> Result<Option<Self>>
     where
         Self: Sized,
     {
-        if let Some(app) = Self::get_app() {
-            Confirm::new()
-                .with_prompt(format!("Include {} theme ?", Self::NAME))
-                .interact()
-                .unwrap()
-                .then_some(Self::get_current(app).unwrap())
+        if let Some(app) = Self::get_app()? {
+            // Without prompts, include every app that's installed
+            (!InputPolicy::get().is_interactive()
+                || Confirm::new()
+                    .with_prompt(format!("Include {} theme ?", Self::NAME))
+                    .interact()
+                    .unwrap())
+            .then(|| Self::get_current(app))
+            .transpose()
         } else {
-            None
+            Ok(None)
         }
     }
 
+    /// Like [`ThemeApp::get_current`], but warns and returns `None` when it can't be read
+    fn try_get_current(app: Self::App) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        Self::get_current(app)
+            .inspect_err(|err| {
+                display_error(format!(
+                    "Couldn't save the current {} theme, it won't be restored: {}",
+                    Self::NAME,
+                    err
+                ))
+            })
+            .ok()
+    }
+
+    fn with_app(&self) -> Result<Option<(&Self, Self::App)>>
+    where
+        Self: Sized,
+    {
+        Ok(Self::get_app()?.map(|app| (self, app)))
+    }
+
+    fn get_plan(&self) -> Result<Option<PlanSection>>
+    where
+        Self: Sized,
+    {
+        Self::get_app()?
+            .map(|app| {
+                Ok(PlanSection {
+                    app: Self::NAME,
+                    actions: self.plan(&app)?,
+                })
+            })
+            .transpose()
+    }
+
     fn get_apply(&self) -> Result<()> {
-        if let Some(app) = Self::get_app() {
+        if let Some(app) = Self::get_app()? {
             self.apply(app)
         } else {
             Ok(())
@@ -59,20 +115,120 @@ pub trait ThemeApp {
 }
 
 impl Theme {
+    /// Saves what this theme is about to overwrite as a restore point, then applies it
+    ///
+    /// An app whose current theme can't be read is left out of the restore point
     pub fn apply(&self) -> Result<()> {
+        self.apply_with_library(&Library::new()?)
+    }
+
+    /// Like [`Theme::apply`], saving the restore point in `library`
+    pub fn apply_with_library(&self, library: &Library) -> Result<()> {
+        let wallpaper_engine = self.wallpaper_engine.as_ref().map(|w| w.with_app());
+        let spicetify = self.spicetify.as_ref().map(|s| s.with_app());
+        let windows = self.windows.as_ref().map(|w| w.with_app());
+        let wallpaper_engine = wallpaper_engine.transpose()?.flatten();
+        let spicetify = spicetify.transpose()?.flatten();
+        let windows = windows.transpose()?.flatten();
+
+        let restore_point = Theme {
+            version: None,
+            author: None,
+            wallpaper_engine: wallpaper_engine
+                .as_ref()
+                .and_then(|(_, app)| ThemeWallpaperEngine::try_get_current(app.clone())),
+            spicetify: spicetify
+                .as_ref()
+                .and_then(|(_, app)| ThemeSpicetify::try_get_current(app.clone())),
+            windows: windows
+                .as_ref()
+                .and_then(|(_, app)| ThemeWindows::try_get_current(app.clone())),
+            base_dir: None,
+        };
+
+        if !restore_point.is_empty() {
+            library.history().push(restore_point)?;
+        }
+
+        if let Some((theme, app)) = wallpaper_engine {
+            theme.apply(app)?;
+        }
+        if let Some((theme, app)) = spicetify {
+            theme.apply(app)?;
+        }
+        if let Some((theme, app)) = windows {
+            theme.apply(app)?;
+        }
+
+        Ok(())
+    }
+
+    /// Walks every section of the theme and lists what applying it would change
+    pub fn plan(&self) -> Result<Plan> {
+        let sections = [
+            self.wallpaper_engine
+                .as_ref()
+                .map(|w| w.get_plan())
+                .transpose()?,
+            self.spicetify.as_ref().map(|s| s.get_plan()).transpose()?,
+            self.windows.as_ref().map(|w| w.get_plan()).transpose()?,
+        ];
+
+        Ok(Plan {
+            sections: sections.into_iter().flatten().flatten().collect(),
+        })
+    }
+
+    /// Applies the theme without saving a restore point
+    pub fn restore(&self) -> Result<()> {
         self.wallpaper_engine
             .as_ref()
             .map(|w| w.get_apply())
             .transpose()?;
 
         self.spicetify.as_ref().map(|s| s.get_apply()).transpose()?;
+        self.windows.as_ref().map(|w| w.get_apply()).transpose()?;
 
         Ok(())
     }
 
+    /// Reads a theme file or a bundle, falling back to an installed theme with that name
     pub fn read_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.exists()
+            && let Some(name) = path.to_str()
+        {
+            return Library::new()?.read_theme(name);
+        }
+
+        if path.to_str().is_some_and(Bundle::is_bundle) {
+            return Self::read_bundle(path, &Library::new()?.cache());
+        }
+
         let content = fs::read_to_string(path)?;
-        Self::parse_json(&content)
+        let mut theme = Self::parse_json(&content)?;
+        let base_dir = fs::canonicalize(path)?
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        theme.resolve_assets(&base_dir)?;
+        theme.base_dir = Some(base_dir);
+        Ok(theme)
+    }
+
+    /// Makes asset paths relative to the theme file absolute
+    pub fn resolve_assets(&mut self, base_dir: &Path) -> Result<()> {
+        if let Some(wallpaper) = self.windows.as_mut().and_then(|w| w.wallpaper.as_mut()) {
+            wallpaper.resolve_assets(base_dir)?;
+        }
+        if let Some(spicetify) = &mut self.spicetify {
+            spicetify.resolve_assets(base_dir)?;
+        }
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.wallpaper_engine.is_none() && self.spicetify.is_none() && self.windows.is_none()
     }
 
     pub fn parse_json(content: &str) -> Result<Self> {
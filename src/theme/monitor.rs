@@ -0,0 +1,331 @@
+use crate::error::Result;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{fmt::Display, process::Command, rc::Rc};
+
+/// A screen as reported by the system
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Monitor {
+    /// Index apps like Wallpaper Engine use to refer to this monitor
+    ///
+    /// On Windows it's the order `EnumDisplayMonitors` lists monitors in, which is assumed to be
+    /// the order of Wallpaper Engine's `-monitor`, themes can select by device name when it isn't
+    pub index: u32,
+    pub device: String,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub primary: bool,
+}
+
+/// Lists the connected monitors, faked in tests
+pub trait MonitorProvider {
+    fn get_monitors(&self) -> Result<Vec<Monitor>>;
+}
+
+impl MonitorProvider for Vec<Monitor> {
+    fn get_monitors(&self) -> Result<Vec<Monitor>> {
+        Ok(self.clone())
+    }
+}
+
+pub type Monitors = Rc<dyn MonitorProvider>;
+
+/// The running system's monitors, from xrandr on Linux
+pub fn get_monitors() -> Option<Monitors> {
+    #[cfg(windows)]
+    return Some(Rc::new(native::NativeMonitors));
+    #[cfg(target_os = "linux")]
+    return Some(Rc::new(XrandrMonitors));
+    #[cfg(not(any(windows, target_os = "linux")))]
+    None
+}
+
+/// Which monitor a wallpaper goes on, stays meaningful across setups
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum MonitorSelector {
+    /// Index as reported by the system
+    Index(u32),
+    Keyword(MonitorKeyword),
+    /// Position counted from the leftmost monitor, starting at 0
+    Position {
+        position: u32,
+    },
+    /// e.g. `2560x1440`
+    Resolution {
+        resolution: String,
+    },
+    /// e.g. `16:9` or `21:9`
+    Aspect {
+        aspect: String,
+    },
+    /// System device name, e.g. `\\.\DISPLAY1`
+    Device {
+        device: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum MonitorKeyword {
+    Primary,
+}
+
+/// What to do with a selector matching none of the monitors, e.g. a theme made for more screens
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum MonitorFallback {
+    /// Leaves it out
+    #[default]
+    Skip,
+    /// Puts it on the primary monitor instead
+    Primary,
+    /// Indexes past the last monitor start over from the first one
+    Wrap,
+}
+
+impl Display for MonitorSelector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MonitorSelector::Index(index) => write!(f, "monitor {}", index),
+            MonitorSelector::Keyword(MonitorKeyword::Primary) => write!(f, "primary monitor"),
+            MonitorSelector::Position { position } => {
+                write!(f, "monitor at position {}", position)
+            }
+            MonitorSelector::Resolution { resolution } => write!(f, "{} monitor", resolution),
+            MonitorSelector::Aspect { aspect } => write!(f, "{} monitor", aspect),
+            MonitorSelector::Device { device } => write!(f, "monitor {}", device),
+        }
+    }
+}
+
+/// Maps selectors onto the monitors of this machine
+pub struct MonitorResolver {
+    /// Sorted left to right
+    monitors: Vec<Monitor>,
+    fallback: MonitorFallback,
+}
+
+impl MonitorResolver {
+    pub fn new(mut monitors: Vec<Monitor>, fallback: MonitorFallback) -> Self {
+        monitors.sort_by_key(|monitor| (monitor.x, monitor.y));
+        Self { monitors, fallback }
+    }
+
+    /// Without a provider, or if listing fails, indexes are trusted as is
+    pub fn from_provider(provider: Option<&Monitors>, fallback: MonitorFallback) -> Self {
+        let monitors = provider
+            .and_then(|provider| provider.get_monitors().ok())
+            .unwrap_or_default();
+        Self::new(monitors, fallback)
+    }
+
+    /// The index to give to apps, `None` if the fallback says to skip it
+    pub fn resolve(&self, selector: &MonitorSelector) -> Option<u32> {
+        self.find(selector).or_else(|| match self.fallback {
+            MonitorFallback::Skip => None,
+            MonitorFallback::Primary => self.primary(),
+            MonitorFallback::Wrap => match selector {
+                MonitorSelector::Index(index) | MonitorSelector::Position { position: index }
+                    if !self.monitors.is_empty() =>
+                {
+                    let mut indexes: Vec<_> = self.monitors.iter().map(|m| m.index).collect();
+                    indexes.sort();
+                    Some(indexes[*index as usize % indexes.len()])
+                }
+                _ => self.primary(),
+            },
+        })
+    }
+
+    /// Whether another selector already got this monitor, when either of them got it through the fallback
+    pub fn collides<T: PartialEq>(
+        &self,
+        selector: &MonitorSelector,
+        monitor: T,
+        picked: &mut Vec<(T, bool)>,
+    ) -> bool {
+        let fallback = self.find(selector).is_none();
+        let collides = picked
+            .iter()
+            .any(|(other, other_fallback)| *other == monitor && (fallback || *other_fallback));
+        picked.push((monitor, fallback));
+        collides
+    }
+
+    /// Position from the left of the monitor with this device name
+    pub fn position_of(&self, device: &str) -> Option<u32> {
+        self.monitors
+            .iter()
+            .position(|monitor| monitor.device.eq_ignore_ascii_case(device))
+            .map(|position| position as u32)
+    }
+
+    fn primary(&self) -> Option<u32> {
+        Some(
+            self.monitors
+                .iter()
+                .find(|monitor| monitor.primary)
+                .or(self.monitors.first())
+                .map_or(0, |monitor| monitor.index),
+        )
+    }
+
+    fn find(&self, selector: &MonitorSelector) -> Option<u32> {
+        if self.monitors.is_empty() {
+            return match selector {
+                MonitorSelector::Index(index) => Some(*index),
+                _ => None,
+            };
+        }
+
+        let monitor = match selector {
+            MonitorSelector::Index(index) => self.monitors.iter().find(|m| m.index == *index),
+            MonitorSelector::Keyword(MonitorKeyword::Primary) => {
+                self.monitors.iter().find(|m| m.primary)
+            }
+            MonitorSelector::Position { position } => self.monitors.get(*position as usize),
+            MonitorSelector::Resolution { resolution } => {
+                let (width, height) = parse_pair(resolution, 'x')?;
+                self.monitors
+                    .iter()
+                    .find(|m| m.width == width && m.height == height)
+            }
+            MonitorSelector::Aspect { aspect } => {
+                let (width, height) = parse_pair(aspect, ':')?;
+                self.monitors
+                    .iter()
+                    .find(|m| m.width as u64 * height as u64 == m.height as u64 * width as u64)
+            }
+            MonitorSelector::Device { device } => self
+                .monitors
+                .iter()
+                .find(|m| m.device.eq_ignore_ascii_case(device)),
+        };
+        monitor.map(|monitor| monitor.index)
+    }
+}
+
+fn parse_pair(value: &str, separator: char) -> Option<(u32, u32)> {
+    let (a, b) = value.split_once(separator)?;
+    Some((a.trim().parse().ok()?, b.trim().parse().ok()?))
+}
+
+/// Monitors listed by `xrandr --listmonitors`, devices are output names like `DP-1`
+pub struct XrandrMonitors;
+
+impl MonitorProvider for XrandrMonitors {
+    fn get_monitors(&self) -> Result<Vec<Monitor>> {
+        let output = Command::new("xrandr").arg("--listmonitors").output()?;
+        Ok(parse_xrandr_monitors(&String::from_utf8_lossy(
+            &output.stdout,
+        )))
+    }
+}
+
+/// Reads lines like ` 0: +*DP-1 2560/597x1440/336+0+0  DP-1`, `*` marking the primary one
+pub fn parse_xrandr_monitors(output: &str) -> Vec<Monitor> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (index, rest) = line.trim().split_once(':')?;
+            let mut parts = rest.split_whitespace();
+            let name = parts.next()?;
+            let geometry = parts.next()?;
+            let (size, offsets) = geometry.split_at(geometry.find(['+', '-'])?);
+            let (width, height) = size.split_once('x')?;
+            let (x, offsets) = parse_xrandr_offset(offsets)?;
+            let (y, _) = parse_xrandr_offset(offsets)?;
+
+            Some(Monitor {
+                index: index.parse().ok()?,
+                device: parts
+                    .next()
+                    .unwrap_or(name.trim_start_matches(['+', '*']))
+                    .to_string(),
+                x,
+                y,
+                width: width.split('/').next()?.parse().ok()?,
+                height: height.split('/').next()?.parse().ok()?,
+                primary: name.contains('*'),
+            })
+        })
+        .collect()
+}
+
+/// Reads the leading offset of a geometry's `+X+Y`, negative ones are written `+-1920` or `-1920`
+fn parse_xrandr_offset(offsets: &str) -> Option<(i32, &str)> {
+    let offsets = offsets.strip_prefix('+').unwrap_or(offsets);
+    // The sign belongs to this offset, the next one starts at the following `+` or `-`
+    let end = offsets
+        .get(1..)?
+        .find(['+', '-'])
+        .map_or(offsets.len(), |i| i + 1);
+    Some((offsets[..end].parse().ok()?, &offsets[end..]))
+}
+
+#[cfg(windows)]
+mod native {
+    use super::{Monitor, MonitorProvider};
+    use crate::error::Result;
+    use windows::{
+        Win32::{
+            Foundation::{LPARAM, RECT},
+            Graphics::Gdi::{
+                EnumDisplayMonitors, GetMonitorInfoW, HDC, HMONITOR, MONITORINFO, MONITORINFOEXW,
+            },
+            UI::WindowsAndMessaging::MONITORINFOF_PRIMARY,
+        },
+        core::BOOL,
+    };
+
+    /// Monitors in `EnumDisplayMonitors` order, the order Wallpaper Engine's indexes are assumed to follow
+    pub struct NativeMonitors;
+
+    unsafe extern "system" fn collect(
+        monitor: HMONITOR,
+        _: HDC,
+        _: *mut RECT,
+        handles: LPARAM,
+    ) -> BOOL {
+        unsafe { (*(handles.0 as *mut Vec<HMONITOR>)).push(monitor) };
+        true.into()
+    }
+
+    impl MonitorProvider for NativeMonitors {
+        fn get_monitors(&self) -> Result<Vec<Monitor>> {
+            let mut handles: Vec<HMONITOR> = vec![];
+            unsafe {
+                EnumDisplayMonitors(
+                    None,
+                    None,
+                    Some(collect),
+                    LPARAM(&mut handles as *mut _ as isize),
+                )
+            }
+            .ok()?;
+
+            let mut monitors = vec![];
+            for (index, handle) in handles.into_iter().enumerate() {
+                let mut info = MONITORINFOEXW::default();
+                info.monitorInfo.cbSize = size_of::<MONITORINFOEXW>() as u32;
+                unsafe { GetMonitorInfoW(handle, &mut info as *mut _ as *mut MONITORINFO) }.ok()?;
+
+                let rect = info.monitorInfo.rcMonitor;
+                let len = info.szDevice.iter().position(|c| *c == 0).unwrap_or(0);
+                monitors.push(Monitor {
+                    index: index as u32,
+                    device: String::from_utf16_lossy(&info.szDevice[..len]),
+                    x: rect.left,
+                    y: rect.top,
+                    width: (rect.right - rect.left) as u32,
+                    height: (rect.bottom - rect.top) as u32,
+                    primary: info.monitorInfo.dwFlags & MONITORINFOF_PRIMARY != 0,
+                });
+            }
+            Ok(monitors)
+        }
+    }
+}
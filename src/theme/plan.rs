@@ -0,0 +1,62 @@
+use crate::theme::windows::system::RegistryValue;
+use colored::Colorize;
+use std::{fmt::Display, path::PathBuf};
+
+/// What applying a theme would do, without doing it
+#[derive(Debug, Default)]
+pub struct Plan {
+    pub sections: Vec<PlanSection>,
+}
+
+#[derive(Debug)]
+pub struct PlanSection {
+    pub app: &'static str,
+    pub actions: Vec<PlannedAction>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlannedAction {
+    /// Runs a program with these arguments
+    Command { program: PathBuf, args: Vec<String> },
+    /// Sets a key in the app's config
+    SetConfig { key: String, value: String },
+    /// Writes a value under `HKEY_CURRENT_USER`
+    SetRegistryValue {
+        path: String,
+        name: String,
+        value: RegistryValue,
+    },
+    /// Anything that doesn't fit the other kinds
+    Other(String),
+}
+
+impl Display for Plan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.sections.is_empty() {
+            return writeln!(f, "Nothing to apply");
+        }
+
+        for section in &self.sections {
+            writeln!(f, "{}", section.app.bold())?;
+            for action in &section.actions {
+                writeln!(f, "  {}", action)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Display for PlannedAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PlannedAction::Command { program, args } => {
+                write!(f, "run {} {}", program.display(), args.join(" "))
+            }
+            PlannedAction::SetConfig { key, value } => write!(f, "set {} = {}", key, value),
+            PlannedAction::SetRegistryValue { path, name, value } => {
+                write!(f, "set HKCU\\{}\\{} = {}", path, name, value)
+            }
+            PlannedAction::Other(description) => f.write_str(description),
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use crate::{
+    error::{Result, UnhandledError},
+    library::{
+        cache::{AssetSource, Cache},
+        check_name,
+    },
+    theme::spicetify::{
+        Spicetify,
+        archive::{extract_from_archive, install_atomically, open_archive},
+        copy_dir,
+    },
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// An extension or a custom app, by name when it's already installed
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(untagged)]
+pub enum SpicetifyAddon {
+    Name(String),
+    Source {
+        /// What it's installed and registered as, e.g. `catJam.js` or `marketplace`
+        name: String,
+        /// Url or local path of a file, a folder or a zip archive
+        source: String,
+        /// File or folder inside the archive, the top folder GitHub adds can be left out
+        path: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpicetifyAddonKind {
+    /// A `.js` file
+    Extension,
+    /// A folder
+    CustomApp,
+}
+
+impl SpicetifyAddonKind {
+    pub fn folder(self) -> &'static str {
+        match self {
+            SpicetifyAddonKind::Extension => "Extensions",
+            SpicetifyAddonKind::CustomApp => "CustomApps",
+        }
+    }
+}
+
+impl Display for SpicetifyAddonKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SpicetifyAddonKind::Extension => "extension",
+            SpicetifyAddonKind::CustomApp => "custom app",
+        })
+    }
+}
+
+impl SpicetifyAddon {
+    pub fn name(&self) -> &str {
+        match self {
+            SpicetifyAddon::Name(name) | SpicetifyAddon::Source { name, .. } => name,
+        }
+    }
+
+    pub fn source(&self) -> Option<&str> {
+        match self {
+            SpicetifyAddon::Name(_) => None,
+            SpicetifyAddon::Source { source, .. } => Some(source),
+        }
+    }
+
+    pub fn resolve_assets(&mut self, base_dir: &Path) -> Result<()> {
+        if let SpicetifyAddon::Source { source, .. } = self {
+            AssetSource::resolve(source, base_dir)?;
+        }
+        Ok(())
+    }
+}
+
+impl Spicetify {
+    pub fn get_addon_path(&self, kind: SpicetifyAddonKind, name: &str) -> Result<PathBuf> {
+        Ok(self.path.join(kind.folder()).join(check_name(name)?))
+    }
+
+    /// Whether the addon has a source and isn't installed yet
+    pub fn needs_install(&self, kind: SpicetifyAddonKind, addon: &SpicetifyAddon) -> bool {
+        addon.source().is_some()
+            && self
+                .get_addon_path(kind, addon.name())
+                .is_ok_and(|path| !path.exists())
+    }
+
+    /// Installs an addon that isn't there yet, a failed install leaves nothing behind
+    pub fn install_addon(
+        &self,
+        kind: SpicetifyAddonKind,
+        addon: &SpicetifyAddon,
+        cache: &Cache,
+    ) -> Result<()> {
+        let SpicetifyAddon::Source { name, source, path } = addon else {
+            return Ok(());
+        };
+        let dest = self.get_addon_path(kind, name)?;
+        if dest.exists() {
+            return Ok(());
+        }
+
+        install_atomically(&dest, |temp| {
+            Self::fetch_addon(kind, source, path.as_deref(), temp, cache)
+        })
+    }
+
+    fn fetch_addon(
+        kind: SpicetifyAddonKind,
+        source: &str,
+        path: Option<&str>,
+        dest: &Path,
+        cache: &Cache,
+    ) -> Result<()> {
+        if let AssetSource::Local(folder) = AssetSource::parse(source, None)?
+            && folder.is_dir()
+        {
+            let source = folder.join(path.unwrap_or_default());
+            if source.is_file() {
+                fs::copy(source, dest)?;
+                return Ok(());
+            }
+            return copy_dir(&source, dest);
+        }
+
+        let file = cache.fetch(source)?;
+        let is_archive = file
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("zip"));
+        if is_archive {
+            extract_from_archive(
+                &mut open_archive(&file)?,
+                &file.to_string_lossy(),
+                path.unwrap_or_default(),
+                dest,
+            )
+        } else if kind == SpicetifyAddonKind::Extension {
+            fs::copy(file, dest)?;
+            Ok(())
+        } else {
+            Err(UnhandledError::InvalidAssetSource(source.to_string()))?
+        }
+    }
+}
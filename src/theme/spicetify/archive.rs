@@ -0,0 +1,135 @@
+use crate::error::{Result, UnhandledError};
+use std::{
+    fs::{self, File},
+    io::{self, Read, Seek},
+    path::{Component, Path, PathBuf},
+};
+use zip::ZipArchive;
+
+/// Fills a sibling temp path then swaps it with `dest`, so a failed install leaves what was there
+pub fn install_atomically(dest: &Path, fill: impl FnOnce(&Path) -> Result<()>) -> Result<()> {
+    let parent = dest.parent().unwrap();
+    fs::create_dir_all(parent)?;
+    let sibling = |suffix: &str| {
+        parent.join(format!(
+            ".{}.{}",
+            dest.file_name().unwrap().to_string_lossy(),
+            suffix
+        ))
+    };
+    let (temp, backup) = (sibling("tmp"), sibling("old"));
+    for leftover in [&temp, &backup] {
+        if leftover.exists() {
+            remove_path(leftover)?;
+        }
+    }
+
+    if let Err(err) = fill(&temp) {
+        if temp.exists() {
+            remove_path(&temp)?;
+        }
+        return Err(err);
+    }
+
+    // Moved aside rather than deleted, so it comes back if the swap fails
+    let replacing = dest.exists();
+    if replacing {
+        fs::rename(dest, &backup)?;
+    }
+    if let Err(err) = fs::rename(&temp, dest) {
+        if replacing {
+            fs::rename(&backup, dest)?;
+        }
+        remove_path(&temp)?;
+        return Err(err.into());
+    }
+    if replacing {
+        remove_path(&backup)?;
+    }
+    Ok(())
+}
+
+pub fn remove_path(path: &Path) -> io::Result<()> {
+    if path.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+pub fn open_archive(path: &Path) -> Result<ZipArchive<File>> {
+    Ok(ZipArchive::new(File::open(path)?)?)
+}
+
+/// Paths of the entries, refusing any that would end up outside of the destination
+pub fn entry_names<R: Read + Seek>(zip: &mut ZipArchive<R>) -> Result<Vec<PathBuf>> {
+    let mut names = vec![];
+    for i in 0..zip.len() {
+        let entry = zip.by_index(i)?;
+        let Some(name) = entry.enclosed_name() else {
+            Err(UnhandledError::UnsafeBundleEntry(entry.name().to_string()))?
+        };
+        names.push(name);
+    }
+    Ok(names)
+}
+
+/// Extracts a file or a folder of an archive to `dest`, keeping the layout under it
+pub fn extract_from_archive<R: Read + Seek>(
+    zip: &mut ZipArchive<R>,
+    source: &str,
+    subpath: &str,
+    dest: &Path,
+) -> Result<()> {
+    let names = entry_names(zip)?;
+    let subpath = Path::new(subpath.trim_matches('/'));
+    let prefix = match archive_top_folder(&names) {
+        // Prefer the path as written, then the same path inside the top folder
+        Some(top) if !names.iter().any(|name| name.starts_with(subpath)) => top.join(subpath),
+        Some(top) if subpath.as_os_str().is_empty() => top,
+        _ => subpath.to_path_buf(),
+    };
+
+    let mut found = false;
+    for (i, name) in names.iter().enumerate() {
+        let Ok(relative) = name.strip_prefix(&prefix) else {
+            continue;
+        };
+        let mut entry = zip.by_index(i)?;
+        let out = if relative.as_os_str().is_empty() {
+            dest.to_path_buf()
+        } else {
+            dest.join(relative)
+        };
+        found = true;
+        if entry.is_dir() {
+            fs::create_dir_all(&out)?;
+            continue;
+        }
+        if let Some(parent) = out.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        io::copy(&mut entry, &mut File::create(out)?)?;
+    }
+
+    if !found {
+        Err(UnhandledError::InvalidAssetSource(format!(
+            "{}/{}",
+            source,
+            prefix.display()
+        )))?
+    }
+    Ok(())
+}
+
+/// The folder every entry is in, like the `repo-main` folder of GitHub archives
+pub fn archive_top_folder(names: &[PathBuf]) -> Option<PathBuf> {
+    let top = match names.first()?.components().next()? {
+        Component::Normal(top) => PathBuf::from(top),
+        _ => return None,
+    };
+    names
+        .iter()
+        .all(|name| name.starts_with(&top) && (name != &top || names.len() > 1))
+        .then_some(top)
+}
@@ -0,0 +1,74 @@
+use crate::{
+    color::RgbaHexColor,
+    error::Result,
+    theme::spicetify::{Spicetify, ThemeSpicetify, ini::IniDocument},
+};
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+/// Scheme swapeme writes a theme's colors to, suffixed with the scheme they change if any
+pub const DEFAULT_SCHEME_NAME: &str = "swapeme";
+
+impl ThemeSpicetify {
+    /// Scheme to select in `config-xpui.ini`, colors never go in the theme's own schemes
+    pub fn scheme_name(&self) -> String {
+        match (&self.color_scheme, &self.colors) {
+            (Some(name), Some(_)) => format!("{}-{}", DEFAULT_SCHEME_NAME, name),
+            (Some(name), None) => name.clone(),
+            (None, Some(_)) => DEFAULT_SCHEME_NAME.to_string(),
+            (None, None) => "Spotify".to_string(),
+        }
+    }
+}
+
+impl Spicetify {
+    pub fn get_color_path(&self, theme: &str) -> Result<PathBuf> {
+        Ok(self.get_theme_path(theme)?.join("color.ini"))
+    }
+
+    /// Writes the theme's colors as a scheme of its `color.ini`
+    pub fn write_color_scheme(&self, theme: &ThemeSpicetify) -> Result<()> {
+        let Some(colors) = &theme.colors else {
+            return Ok(());
+        };
+        let path = self.get_color_path(&theme.name)?;
+        let content = fs::read_to_string(&path).unwrap_or_default();
+        Ok(fs::write(
+            path,
+            merge_color_scheme(
+                &content,
+                theme.color_scheme.as_deref(),
+                &theme.scheme_name(),
+                colors,
+            ),
+        )?)
+    }
+}
+
+/// Rebuilds the scheme in `color.ini` from the base scheme's colors and the given ones, other schemes are kept
+pub fn merge_color_scheme(
+    content: &str,
+    base: Option<&str>,
+    scheme: &str,
+    colors: &BTreeMap<String, RgbaHexColor>,
+) -> String {
+    let mut ini = IniDocument::parse(content);
+    ini.remove_section(scheme);
+    let base_entries: Vec<(String, String)> = base
+        .map(|base| ini.entries(base))
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+    for (key, value) in base_entries {
+        ini.set(scheme, &key, &value);
+    }
+    for (key, color) in colors {
+        ini.set(scheme, key, &to_spicetify_hex(*color));
+    }
+    ini.to_string()
+}
+
+/// `RRGGBB` without a `#`, spicetify has no alpha
+pub fn to_spicetify_hex(color: RgbaHexColor) -> String {
+    format!("{:02X}{:02X}{:02X}", color.0.r, color.0.g, color.0.b)
+}
@@ -0,0 +1,259 @@
+use crate::{
+    error::{Result, UnhandledError},
+    theme::spicetify::{
+        Spicetify, ThemeSpicetify, addon::SpicetifyAddon, error::SpicetifyConfigNotFoundError,
+        ini::IniDocument,
+    },
+};
+use serde::{Deserialize, Serialize};
+use std::{fmt::Display, fs, path::PathBuf};
+
+impl Spicetify {
+    const CONFIG_FILE: &str = "config-xpui.ini";
+
+    /// Next to the themes, asking spicetify only when it isn't there
+    pub fn get_config_path(&self) -> Result<PathBuf> {
+        let path = self.path.join(Self::CONFIG_FILE);
+        if path.is_file() {
+            return Ok(path);
+        }
+        let output = self
+            .invoke_command()
+            .arg("--config")
+            .output()
+            .map_err(|_| SpicetifyConfigNotFoundError(self.clone()))?;
+        match String::from_utf8_lossy(&output.stdout).lines().next() {
+            Some(path) if !path.trim().is_empty() => Ok(PathBuf::from(path.trim())),
+            _ => Err(SpicetifyConfigNotFoundError(self.clone()).into()),
+        }
+    }
+
+    pub fn read_config(&self) -> Result<SpicetifyConfig> {
+        let path = self.get_config_path()?;
+        if !path.is_file() {
+            Err(SpicetifyConfigNotFoundError(self.clone()))?
+        }
+        SpicetifyConfig::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn write_config(&self, config: &SpicetifyConfig) -> Result<()> {
+        Ok(fs::write(self.get_config_path()?, config.to_string())?)
+    }
+
+    /// Kept in spicetify's folder, so it follows the installation it describes
+    pub fn get_owned_path(&self) -> PathBuf {
+        self.path.join("swapeme.json")
+    }
+
+    pub fn read_owned(&self) -> Result<SpicetifyOwned> {
+        let path = self.get_owned_path();
+        if !path.exists() {
+            return Ok(SpicetifyOwned::default());
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn write_owned(&self, owned: &SpicetifyOwned) -> Result<()> {
+        Ok(fs::write(
+            self.get_owned_path(),
+            serde_json::to_string_pretty(owned)?,
+        )?)
+    }
+
+    /// Config keys to set for a theme, leaving what's already set and the user's own entries alone
+    pub fn theme_changes(
+        theme: &ThemeSpicetify,
+        current: Option<&SpicetifyConfig>,
+        owned: &SpicetifyOwned,
+    ) -> SpicetifyChanges {
+        let mut config = vec![
+            ("current_theme", theme.name.clone()),
+            ("color_scheme", theme.scheme_name()),
+        ];
+        for (key, value) in theme.flags() {
+            if let Some(value) = value {
+                config.push((key, if value { "1" } else { "0" }.to_string()));
+            }
+        }
+        if let Some(current) = current {
+            config.retain(|(key, value)| current.get(key) != Some(value.as_str()));
+        }
+
+        let mut changes = SpicetifyChanges {
+            config,
+            owned: owned.clone(),
+        };
+        if let Some(extensions) = &theme.extensions {
+            changes.owned.extensions = changes.diff_list(
+                "extensions",
+                &names(extensions),
+                current.map(SpicetifyConfig::extensions),
+                &owned.extensions,
+            );
+        }
+        if let Some(custom_apps) = &theme.custom_apps {
+            changes.owned.custom_apps = changes.diff_list(
+                "custom_apps",
+                &names(custom_apps),
+                current.map(SpicetifyConfig::custom_apps),
+                &owned.custom_apps,
+            );
+        }
+        changes
+    }
+}
+
+/// Extensions and custom apps swapeme added, the only ones it ever removes
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SpicetifyOwned {
+    #[serde(default)]
+    pub extensions: Vec<String>,
+    #[serde(default)]
+    pub custom_apps: Vec<String>,
+}
+
+/// What applying a theme changes, see [`Spicetify::theme_changes`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpicetifyChanges {
+    /// Arguments of `spicetify config`, a trailing `-` removes an entry from a list
+    pub config: Vec<(&'static str, String)>,
+    /// What swapeme owns once they're applied
+    pub owned: SpicetifyOwned,
+}
+
+impl SpicetifyChanges {
+    /// Whether `spicetify refresh` is enough, which only works on an already patched Spotify
+    pub fn only_theme(&self) -> bool {
+        self.config
+            .iter()
+            .all(|(key, _)| ["current_theme", "color_scheme"].contains(key))
+    }
+
+    /// Adds what's missing, removes what swapeme added and the theme no longer wants
+    fn diff_list(
+        &mut self,
+        key: &'static str,
+        wanted: &[String],
+        current: Option<Vec<String>>,
+        owned: &[String],
+    ) -> Vec<String> {
+        // Without the current config, assume everything swapeme added is still there
+        let is_set = |entry: &String| {
+            current
+                .as_ref()
+                .map_or(owned.contains(entry), |current| current.contains(entry))
+        };
+
+        let mut new_owned = vec![];
+        for entry in owned {
+            if wanted.contains(entry) && is_set(entry) {
+                new_owned.push(entry.clone());
+            } else if is_set(entry) {
+                self.config.push((key, format!("{}-", entry)));
+            }
+        }
+        for entry in wanted {
+            if !is_set(entry) {
+                self.config.push((key, entry.clone()));
+                new_owned.push(entry.clone());
+            }
+        }
+        new_owned
+    }
+}
+
+const SETTING: &str = "Setting";
+const ADDITIONAL_OPTIONS: &str = "AdditionalOptions";
+/// Keys holding `|` separated lists
+const LIST_KEYS: [&str; 2] = ["extensions", "custom_apps"];
+
+/// `config-xpui.ini`, edited in place so the user's comments and key order are kept
+#[derive(Debug, Clone)]
+pub struct SpicetifyConfig {
+    document: IniDocument,
+}
+
+impl SpicetifyConfig {
+    pub fn parse(content: &str) -> Result<Self> {
+        let document = IniDocument::parse(content);
+        if document.get(SETTING, "current_theme").is_none() {
+            Err(UnhandledError::InvalidSpicetifyConfig(
+                "missing current_theme".to_string(),
+            ))?
+        }
+        Ok(Self { document })
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.document.get(section_of(key), key)
+    }
+
+    pub fn current_theme(&self) -> &str {
+        self.get("current_theme").unwrap_or_default()
+    }
+
+    /// Flags are written `1` or `0`
+    pub fn flag(&self, key: &str) -> Option<bool> {
+        match self.get(key)? {
+            "1" => Some(true),
+            "0" => Some(false),
+            _ => None,
+        }
+    }
+
+    pub fn extensions(&self) -> Vec<String> {
+        split_list(self.get("extensions").unwrap_or_default())
+    }
+
+    pub fn custom_apps(&self) -> Vec<String> {
+        split_list(self.get("custom_apps").unwrap_or_default())
+    }
+
+    /// Applies [`SpicetifyChanges::config`] the way `spicetify config` would
+    pub fn set_all(&mut self, changes: &[(&str, String)]) {
+        for (key, value) in changes {
+            if !LIST_KEYS.contains(key) {
+                self.document.set(SETTING, key, value);
+                continue;
+            }
+
+            let mut list = split_list(self.get(key).unwrap_or_default());
+            match value.strip_suffix('-') {
+                Some(entry) => list.retain(|item| item != entry),
+                None if !list.contains(value) => list.push(value.clone()),
+                None => {}
+            }
+            self.document.set(ADDITIONAL_OPTIONS, key, &list.join("|"));
+        }
+    }
+}
+
+impl Display for SpicetifyConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.document.fmt(f)
+    }
+}
+
+fn section_of(key: &str) -> &'static str {
+    if LIST_KEYS.contains(&key) {
+        ADDITIONAL_OPTIONS
+    } else {
+        SETTING
+    }
+}
+
+fn names(addons: &[SpicetifyAddon]) -> Vec<String> {
+    addons
+        .iter()
+        .map(|addon| addon.name().to_string())
+        .collect()
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split('|')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(String::from)
+        .collect()
+}
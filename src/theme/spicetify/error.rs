@@ -1,9 +1,12 @@
-use std::fmt::Display;
+use std::{fmt::Display, path::PathBuf};
 
 use crate::{
-    cli::{start_cmd, wait_for_user},
+    cli::{InputPolicy, display_error, display_success, open_for_user, wait_for_user},
     error::HandledError,
+    library::Library,
+    theme::spicetify::Spicetify,
 };
+use dialoguer::Input;
 
 pub struct SpicetifyNotInstalledError {}
 
@@ -15,11 +18,84 @@ impl Display for SpicetifyNotInstalledError {
 
 impl HandledError for SpicetifyNotInstalledError {
     fn action(&self) -> &'static str {
-        "Install spicetify for spotify theme"
+        "Set spicetify's path or install it"
     }
-    fn handle(&self) {
-        start_cmd("https://spicetify.app/").unwrap();
-        wait_for_user("spicetify installation");
+    fn handle(&self, policy: InputPolicy) {
+        let Ok(library) = Library::new() else {
+            return display_error("Couldn't find where to store settings");
+        };
+        if !policy.is_interactive() {
+            return display_error(format!(
+                "Install spicetify from https://spicetify.app/ or set paths.spicetify in {}",
+                library.get_settings_path().display()
+            ));
+        }
+
+        let path: String = Input::new()
+            .with_prompt("Path to spicetify's folder, leave empty to install it")
+            .allow_empty(true)
+            .interact_text()
+            .unwrap();
+        if path.trim().is_empty() {
+            open_for_user("https://spicetify.app/", policy);
+            return wait_for_user("spicetify installation", policy);
+        }
+        match Spicetify::save_path_override(&library, PathBuf::from(path.trim().trim_matches('"')))
+        {
+            Ok(_) => display_success("Saved spicetify's path"),
+            Err(_) => display_error(format!("{} doesn't contain spicetify", path.trim())),
+        }
+    }
+    fn item(&self) -> &'static str {
+        "spotify theme"
+    }
+}
+
+/// A spicetify command that failed, e.g. `apply` after Spotify updated itself
+pub struct SpicetifyCommandFailedError {
+    pub command: String,
+    /// What spicetify printed on stderr, or on stdout when stderr was empty
+    pub message: String,
+}
+
+impl Display for SpicetifyCommandFailedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "spicetify {} failed: {}", self.command, self.message)
+    }
+}
+
+impl HandledError for SpicetifyCommandFailedError {
+    fn action(&self) -> &'static str {
+        "Retry it"
+    }
+    fn handle(&self, policy: InputPolicy) {
+        if policy.is_interactive() {
+            println!("After a Spotify update, running spicetify backup apply usually fixes it");
+        }
+        wait_for_user("the fix", policy);
+    }
+    fn item(&self) -> &'static str {
+        "spotify theme"
+    }
+}
+
+pub struct SpicetifyConfigNotFoundError(pub Spicetify);
+
+impl Display for SpicetifyConfigNotFoundError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Spicetify's config-xpui.ini not found")
+    }
+}
+
+impl HandledError for SpicetifyConfigNotFoundError {
+    fn action(&self) -> &'static str {
+        "Let spicetify create it"
+    }
+    fn handle(&self, _policy: InputPolicy) {
+        // Spicetify writes its default config on its first run
+        if let Err(err) = self.0.invoke_command().arg("config").output() {
+            display_error(format!("Couldn't run spicetify: {}", err));
+        }
     }
     fn item(&self) -> &'static str {
         "spotify theme"
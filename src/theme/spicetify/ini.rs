@@ -0,0 +1,131 @@
+use std::fmt::Display;
+
+/// An ini file edited in place, comments and lines swapeme doesn't touch are kept as they are
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IniDocument {
+    lines: Vec<String>,
+    /// Written back with the file's own line endings
+    crlf: bool,
+}
+
+impl IniDocument {
+    pub fn parse(content: &str) -> Self {
+        Self {
+            lines: content.lines().map(String::from).collect(),
+            crlf: content.contains("\r\n"),
+        }
+    }
+
+    pub fn sections(&self) -> Vec<&str> {
+        self.lines
+            .iter()
+            .filter_map(|line| section_name(line))
+            .collect()
+    }
+
+    pub fn get(&self, section: &str, key: &str) -> Option<&str> {
+        let (start, end) = self.section_range(section)?;
+        self.lines[start..end]
+            .iter()
+            .find_map(|line| split_entry(line).filter(|(name, _)| *name == key))
+            .map(|(_, value)| value)
+    }
+
+    /// Entries of a section in the order they're written
+    pub fn entries(&self, section: &str) -> Vec<(&str, &str)> {
+        let Some((start, end)) = self.section_range(section) else {
+            return vec![];
+        };
+        self.lines[start..end]
+            .iter()
+            .filter_map(|line| split_entry(line))
+            .collect()
+    }
+
+    /// Removes a section with its header
+    pub fn remove_section(&mut self, section: &str) {
+        if let Some((start, end)) = self.section_range(section) {
+            self.lines.drain(start - 1..end);
+        }
+    }
+
+    /// Updates the entry where it is, or adds it at the end of its section, creating the section if needed
+    pub fn set(&mut self, section: &str, key: &str, value: &str) {
+        let Some((start, end)) = self.section_range(section) else {
+            if self
+                .lines
+                .last()
+                .is_some_and(|line| !line.trim().is_empty())
+            {
+                self.lines.push(String::new());
+            }
+            self.lines.push(format!("[{}]", section));
+            self.lines.push(format!("{} = {}", key, value));
+            return;
+        };
+
+        for line in &mut self.lines[start..end] {
+            if split_entry(line).is_some_and(|(name, _)| name == key) {
+                // Keeps the alignment of the value
+                let separator = line.find('=').unwrap();
+                let value_start = line[separator + 1..]
+                    .find(|c: char| !c.is_whitespace())
+                    .map_or(line.len(), |offset| separator + 1 + offset);
+                let indent = if value_start == line.len() && !line.ends_with(' ') {
+                    " "
+                } else {
+                    ""
+                };
+                *line = format!("{}{}{}", &line[..value_start], indent, value);
+                return;
+            }
+        }
+
+        // After the last entry, so blank lines between sections stay where they are
+        let last = (start..end)
+            .rev()
+            .find(|i| !self.lines[*i].trim().is_empty())
+            .unwrap_or(start - 1);
+        self.lines.insert(last + 1, format!("{} = {}", key, value));
+    }
+
+    /// Lines of a section's entries, without its header
+    fn section_range(&self, section: &str) -> Option<(usize, usize)> {
+        let header = self
+            .lines
+            .iter()
+            .position(|line| section_name(line) == Some(section))?;
+        let end = self.lines[header + 1..]
+            .iter()
+            .position(|line| section_name(line).is_some())
+            .map_or(self.lines.len(), |offset| header + 1 + offset);
+        Some((header + 1, end))
+    }
+}
+
+impl Display for IniDocument {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let newline = if self.crlf { "\r\n" } else { "\n" };
+        for line in &self.lines {
+            write!(f, "{}{}", line, newline)?;
+        }
+        Ok(())
+    }
+}
+
+fn section_name(line: &str) -> Option<&str> {
+    line.trim()
+        .strip_prefix('[')?
+        .strip_suffix(']')
+        .map(str::trim)
+}
+
+/// `key = value`, `None` for comments and anything else
+fn split_entry(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim();
+    if line.starts_with([';', '#']) {
+        return None;
+    }
+    let (key, value) = line.split_once('=')?;
+    Some((key.trim(), value.trim()))
+}
@@ -1,205 +1,461 @@
+pub mod addon;
+pub mod archive;
+pub mod color;
+pub mod config;
 pub mod error;
+pub mod ini;
+#[cfg(test)]
+mod test;
 
 use crate::{
-    cli::{display_error, display_success},
+    cli::{InputPolicy, display_error, display_success},
+    color::RgbaHexColor,
     error::{Error, HandledError, Result, UnhandledError},
-    theme::{ThemeApp, spicetify::error::SpicetifyNotInstalledError},
+    library::{Library, cache::AssetSource, check_name, settings::Settings},
+    theme::{
+        ThemeApp,
+        plan::PlannedAction,
+        spicetify::{
+            addon::{SpicetifyAddon, SpicetifyAddonKind},
+            archive::{entry_names, extract_from_archive, install_atomically},
+            config::SpicetifyChanges,
+            error::{SpicetifyCommandFailedError, SpicetifyNotInstalledError},
+        },
+    },
 };
 use reqwest::blocking::get;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::{
+    collections::BTreeMap,
     fmt::Display,
-    fs::{self, File},
-    io::{self, Cursor, Read},
-    path::PathBuf,
+    fs,
+    io::Cursor,
+    path::{Path, PathBuf},
     process::Command,
 };
 use zip::ZipArchive;
 
 #[derive(Clone)]
 pub struct Spicetify {
-    is_global: bool,
+    /// Userdata folder, with `Themes`, `Extensions` and `config-xpui.ini`
     path: PathBuf,
+    /// The binary, only its name when it's on the PATH
+    program: PathBuf,
 }
 
 #[derive(Deserialize, Serialize, JsonSchema, Clone)]
 pub struct ThemeSpicetify {
     name: String,
+    /// Scheme of the theme's `color.ini`, `colors` go in a copy of it named `swapeme-<scheme>`
     color_scheme: Option<String>,
+    /// Colors written to a scheme of swapeme's in the theme's `color.ini`, keyed like `text` or `sidebar`
+    colors: Option<BTreeMap<String, RgbaHexColor>>,
+    /// Zip to download the theme from, or a theme folder, e.g. shipped in a bundle
     url: Option<String>,
+    /// Theme folder inside the zip or folder, found by its `color.ini` when left out
+    path: Option<String>,
+    /// Extension files, e.g. `fullAppDisplay.js`, the user's own extensions are kept
+    extensions: Option<Vec<SpicetifyAddon>>,
+    /// Custom app folders, e.g. `marketplace`, the user's own apps are kept
+    custom_apps: Option<Vec<SpicetifyAddon>>,
+    inject_css: Option<bool>,
+    replace_colors: Option<bool>,
+    overwrite_assets: Option<bool>,
+    inject_theme_js: Option<bool>,
 }
 
 impl ThemeApp for ThemeSpicetify {
     const NAME: &'static str = "Spotify (Spicetify)";
     type App = Spicetify;
 
-    fn get_app() -> Option<Self::App> {
+    fn get_app() -> Result<Option<Self::App>> {
         Error::error_prone_step(&|| Spicetify::new(), None)
     }
 
     fn apply(&self, app: Spicetify) -> Result<()> {
         Error::error_prone_step(
-            &|| Ok(app.set_theme(&self)?),
+            &|| app.set_theme(self),
             Some(&format!("Applied theme {} to spotify", self.name)),
-        );
+        )?;
         Ok(())
     }
 
+    fn plan(&self, app: &Spicetify) -> Result<Vec<PlannedAction>> {
+        let mut actions = vec![];
+        if !app.get_color_path(&self.name)?.exists() {
+            actions.push(PlannedAction::Other(match &self.url {
+                Some(url) => format!("download theme {} from {}", self.name, url),
+                None => format!("theme {} is not installed", self.name),
+            }));
+        }
+
+        for (kind, addon) in self.addons() {
+            if addon.source().is_some() && app.get_addon_path(kind, addon.name()).is_err() {
+                actions.push(PlannedAction::Other(format!(
+                    "skip {} {}, it isn't a plain file name",
+                    kind,
+                    addon.name()
+                )));
+            } else if app.needs_install(kind, addon) {
+                actions.push(PlannedAction::Other(format!(
+                    "install {} {} from {}",
+                    kind,
+                    addon.name(),
+                    addon.source().unwrap()
+                )));
+            }
+        }
+
+        if self.colors.is_some() {
+            actions.push(PlannedAction::Other(format!(
+                "write color scheme {} to {}",
+                self.scheme_name(),
+                app.get_color_path(&self.name)?.display()
+            )));
+        }
+
+        let current = app.read_config().ok();
+        let changes = Spicetify::theme_changes(
+            self,
+            current.as_ref(),
+            &app.read_owned().unwrap_or_default(),
+        );
+        let command = Spicetify::apply_command(current.is_some(), &changes);
+        for (key, value) in changes.config {
+            actions.push(PlannedAction::SetConfig {
+                key: key.to_string(),
+                value,
+            });
+        }
+
+        actions.push(PlannedAction::Command {
+            program: app.get_program(),
+            args: vec![command.to_string()],
+        });
+        Ok(actions)
+    }
+
     fn get_current(app: Self::App) -> Result<Self>
     where
         Self: Sized,
     {
-        let setting = app.read_config()?.setting;
+        let config = app.read_config()?;
         Ok(ThemeSpicetify {
-            name: setting.current_theme,
-            color_scheme: setting.color_scheme,
+            name: config.current_theme().to_string(),
+            color_scheme: config.get("color_scheme").map(String::from),
+            colors: None,
             url: None,
+            path: None,
+            extensions: Some(
+                config
+                    .extensions()
+                    .into_iter()
+                    .map(SpicetifyAddon::Name)
+                    .collect(),
+            ),
+            custom_apps: Some(
+                config
+                    .custom_apps()
+                    .into_iter()
+                    .map(SpicetifyAddon::Name)
+                    .collect(),
+            ),
+            inject_css: config.flag("inject_css"),
+            replace_colors: config.flag("replace_colors"),
+            overwrite_assets: config.flag("overwrite_assets"),
+            inject_theme_js: config.flag("inject_theme_js"),
         })
     }
 }
 
-impl Spicetify {
-    const APP_NAME: &str = "spicetify";
-    const EXE_NAME: &str = "spicetify.exe";
+impl ThemeSpicetify {
+    /// Flags of `config-xpui.ini`, `None` leaves them as they are
+    pub fn flags(&self) -> [(&'static str, Option<bool>); 4] {
+        [
+            ("inject_css", self.inject_css),
+            ("replace_colors", self.replace_colors),
+            ("overwrite_assets", self.overwrite_assets),
+            ("inject_theme_js", self.inject_theme_js),
+        ]
+    }
 
-    pub fn new() -> Result<Self> {
-        let (path, is_global) = {
-            match Command::new(Self::APP_NAME).arg("path").output() {
-                Ok(output) => {
-                    let output_str = String::from_utf8(output.stdout).unwrap();
-                    let path = PathBuf::from(output_str.lines().next().unwrap());
-                    (path, true)
-                }
-                Err(_err) => {
-                    let path = dirs::cache_dir().unwrap().join(Self::APP_NAME);
-                    Command::new(path.join(Self::EXE_NAME))
-                        .spawn()
-                        .map_err(|_| SpicetifyNotInstalledError {})?;
-                    (path, false)
-                }
-            }
+    /// Extensions and custom apps, with their kind
+    pub fn addons(&self) -> impl Iterator<Item = (SpicetifyAddonKind, &SpicetifyAddon)> {
+        let extensions = self.extensions.iter().flatten();
+        let custom_apps = self.custom_apps.iter().flatten();
+        extensions
+            .map(|addon| (SpicetifyAddonKind::Extension, addon))
+            .chain(custom_apps.map(|addon| (SpicetifyAddonKind::CustomApp, addon)))
+    }
+
+    /// The same theme without some of its addons, e.g. the ones that failed to install
+    pub fn without_addons(&self, skipped: &[(SpicetifyAddonKind, String)]) -> Self {
+        let keep = |kind: SpicetifyAddonKind, addons: &Option<Vec<SpicetifyAddon>>| {
+            addons.as_ref().map(|addons| {
+                addons
+                    .iter()
+                    .filter(|addon| !skipped.contains(&(kind, addon.name().to_string())))
+                    .cloned()
+                    .collect()
+            })
         };
+        Self {
+            extensions: keep(SpicetifyAddonKind::Extension, &self.extensions),
+            custom_apps: keep(SpicetifyAddonKind::CustomApp, &self.custom_apps),
+            ..self.clone()
+        }
+    }
 
-        Ok(Spicetify { is_global, path })
+    pub fn resolve_assets(&mut self, base_dir: &Path) -> Result<()> {
+        // Checked when the theme is read, long before anything gets written there
+        check_name(&self.name)?;
+        if let Some(url) = &mut self.url {
+            AssetSource::resolve(url, base_dir)?;
+        }
+        for addon in self
+            .extensions
+            .iter_mut()
+            .chain(self.custom_apps.iter_mut())
+            .flatten()
+        {
+            addon.resolve_assets(base_dir)?;
+        }
+        Ok(())
     }
+}
 
-    pub fn invoke_command(&self) -> Command {
-        let cmd = if self.is_global {
-            Command::new(Self::APP_NAME)
+fn copy_dir(from: &Path, to: &Path) -> Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let path = entry.path();
+        if path.is_dir() {
+            copy_dir(&path, &to.join(entry.file_name()))?;
         } else {
-            let exe_path = self.path.join(Self::EXE_NAME);
-            Command::new(exe_path)
-        };
-        cmd
+            fs::copy(&path, to.join(entry.file_name()))?;
+        }
     }
+    Ok(())
+}
 
-    pub fn config(&self) -> Command {
-        let mut cmd = self.invoke_command();
-        cmd.arg("config");
-        cmd
+impl Spicetify {
+    const APP_NAME: &str = "spicetify";
+    const EXE_NAME: &str = if cfg!(windows) {
+        "spicetify.exe"
+    } else {
+        "spicetify"
+    };
+
+    pub fn new() -> Result<Self> {
+        Self::new_with_settings(&Settings::load())
     }
 
-    pub fn set_color_scheme(&self, color_scheme: &str) -> Result<()> {
-        self.config()
-            .arg("color_scheme")
-            .arg(color_scheme)
-            .output()?;
-        Ok(())
+    /// Uses the folder set in the settings, then the PATH, then the default install folder
+    pub fn new_with_settings(settings: &Settings) -> Result<Self> {
+        if let Some(dir) = &settings.paths.spicetify
+            && Self::is_installation(dir)
+        {
+            return Self::new_with_program(Self::program_in(dir));
+        }
+
+        Self::new_with_program(PathBuf::from(Self::APP_NAME)).or_else(|_| {
+            let dir = dirs::cache_dir()
+                .ok_or(SpicetifyNotInstalledError {})?
+                .join(Self::APP_NAME);
+            Self::new_with_program(Self::program_in(&dir))
+        })
     }
 
-    pub fn apply(&self) -> Result<()> {
-        self.invoke_command().arg("apply").output()?;
-        Ok(())
+    /// Asks the binary where its userdata folder is, which isn't the folder the binary is in
+    pub fn new_with_program(program: PathBuf) -> Result<Self> {
+        let output = Command::new(&program)
+            .args(["path", "userdata"])
+            .output()
+            .map_err(|_| SpicetifyNotInstalledError {})?;
+        let path = String::from_utf8(output.stdout)
+            .ok()
+            .filter(|_| output.status.success())
+            .and_then(|stdout| {
+                stdout
+                    .lines()
+                    .map(str::trim)
+                    .find(|line| !line.is_empty())
+                    .map(PathBuf::from)
+            })
+            .ok_or(SpicetifyNotInstalledError {})?;
+        Ok(Self::new_with_path(path, program))
     }
 
-    pub fn get_theme_path(&self, name: &str) -> PathBuf {
-        self.path.join("Themes").join(name)
+    pub fn new_with_path(path: PathBuf, program: PathBuf) -> Self {
+        Spicetify { path, program }
     }
 
-    pub fn set_theme(&self, theme: &ThemeSpicetify) -> Result<()> {
-        if !self.get_theme_path(&theme.name).join("color.ini").exists() {
-            return Err(SpicetifyThemeNotFoundError(self.clone(), theme.clone()).into());
+    /// Where the binary is in a folder spicetify is installed in
+    pub fn program_in(dir: &Path) -> PathBuf {
+        dir.join(Self::EXE_NAME)
+    }
+
+    pub fn is_installation(dir: &Path) -> bool {
+        Self::program_in(dir).is_file()
+    }
+
+    /// Saves a folder given by the user as spicetify's
+    pub fn save_path_override(library: &Library, path: PathBuf) -> Result<()> {
+        if !Self::is_installation(&path) {
+            Err(UnhandledError::InvalidAppPath(path.clone()))?
         }
+        let mut settings = library.read_settings()?;
+        settings.paths.spicetify = Some(path);
+        library.write_settings(&settings)
+    }
 
-        self.config()
-            .arg("current_theme")
-            .arg(&theme.name)
-            .output()?;
+    pub fn get_program(&self) -> PathBuf {
+        self.program.clone()
+    }
 
-        self.set_color_scheme(
-            &theme
-                .color_scheme
-                .as_ref()
-                .unwrap_or(&"Spotify".to_string()),
-        )?;
+    pub fn invoke_command(&self) -> Command {
+        Command::new(self.get_program())
+    }
 
-        self.apply()?;
+    /// Runs a spicetify command, which can fail with a zero exit status but still says so on stderr
+    pub fn run(&self, command: &str) -> Result<()> {
+        let output = self.invoke_command().arg(command).output()?;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !output.status.success() || stderr.to_lowercase().contains("error") {
+            let message = match stderr.trim() {
+                "" => String::from_utf8_lossy(&output.stdout).trim().to_string(),
+                stderr => stderr.to_string(),
+            };
+            Err(SpicetifyCommandFailedError {
+                command: command.to_string(),
+                message,
+            })?
+        }
         Ok(())
     }
 
-    pub fn download_theme(&self, theme: &ThemeSpicetify) -> Result<()> {
-        let Some(url) = &theme.url else {
-            Err(UnhandledError::FailedToDownloadTheme)?
-        };
+    pub fn apply(&self) -> Result<()> {
+        self.run("apply")
+    }
 
-        let mut response = get(url)?;
+    /// Reloads the theme's files, much faster than patching Spotify again
+    pub fn refresh(&self) -> Result<()> {
+        self.run("refresh")
+    }
 
-        if !response.status().is_success() {
-            panic!("" /* TODO */)
+    /// `refresh` when only the theme changed on a Spotify spicetify already knows, `apply` otherwise
+    pub fn apply_command(has_config: bool, changes: &SpicetifyChanges) -> &'static str {
+        if has_config && changes.only_theme() {
+            "refresh"
+        } else {
+            "apply"
         }
+    }
 
-        let path = self.get_theme_path(&theme.name);
-        fs::create_dir_all(&path)?;
+    pub fn get_theme_path(&self, name: &str) -> Result<PathBuf> {
+        Ok(self.path.join("Themes").join(check_name(name)?))
+    }
 
-        let mut content = Vec::new();
-        response.read_to_end(&mut content)?;
-        let reader = Cursor::new(content);
-        let mut zip = ZipArchive::new(reader)?;
-        for i in 0..zip.len() {
-            let mut file = zip.by_index(i)?;
-            let file_name = file.name().to_owned();
-            if file_name.contains(&theme.name)
-                && ["user.css", "color.ini", "theme.js"]
-                    .iter()
-                    .find(|name| file_name.contains(*name))
-                    .is_some()
-            {
-                let out =
-                    &mut File::create(path.join(PathBuf::from(file_name).file_name().unwrap()))?;
-                io::copy(&mut file, out)?;
-            }
+    pub fn set_theme(&self, theme: &ThemeSpicetify) -> Result<()> {
+        if !self.get_color_path(&theme.name)?.exists() {
+            return Err(SpicetifyThemeNotFoundError(self.clone(), theme.clone()).into());
         }
 
-        Ok(())
+        // Addons have to be there before they're registered, the ones that can't be are left out
+        let cache = Library::new()?.cache();
+        let mut skipped = vec![];
+        for (kind, addon) in theme.addons() {
+            if let Err(err) = self.install_addon(kind, addon, &cache) {
+                display_error(format!(
+                    "Couldn't install {} {}, skipping it: {:?}",
+                    kind,
+                    addon.name(),
+                    err
+                ));
+                skipped.push((kind, addon.name().to_string()));
+            }
+        }
+        let theme = &theme.without_addons(&skipped);
+        self.write_color_scheme(theme)?;
+
+        let mut config = self.read_config()?;
+        let changes = Self::theme_changes(theme, Some(&config), &self.read_owned()?);
+        config.set_all(&changes.config);
+        self.write_config(&config)?;
+        self.write_owned(&changes.owned)?;
+
+        if changes.only_theme() {
+            // A Spotify update undoes the patch, which only apply redoes
+            self.refresh().or_else(|_| self.apply())
+        } else {
+            self.apply()
+        }
     }
 
-    pub fn get_config_path(&self) -> Result<PathBuf> {
-        Ok(PathBuf::from(
-            String::from_utf8(self.invoke_command().arg("--config").output()?.stdout)
-                .unwrap()
-                .lines()
-                .next()
-                .unwrap(),
-        ))
-    }
+    /// Installs the theme's folder as a whole, replacing what's there only once it succeeded
+    pub fn download_theme(&self, theme: &ThemeSpicetify) -> Result<()> {
+        let Some(url) = &theme.url else {
+            Err(UnhandledError::FailedToDownloadTheme)?
+        };
 
-    pub fn read_config(&self) -> Result<SpicetifyConfig> {
-        let content = fs::read_to_string(self.get_config_path()?)?;
-        Ok(serde_ini::from_str(&content).unwrap())
+        install_atomically(&self.get_theme_path(&theme.name)?, |temp| {
+            Self::fetch_theme(theme, url, temp)?;
+            if !temp.join("color.ini").is_file() {
+                Err(UnhandledError::InvalidSpicetifyTheme(url.clone()))?
+            }
+            Ok(())
+        })
     }
-}
 
-#[derive(Debug, Deserialize)]
-pub struct SpicetifyConfig {
-    #[serde(rename = "Setting")]
-    setting: SpicetifyConfigSetting,
+    fn fetch_theme(theme: &ThemeSpicetify, url: &str, dest: &Path) -> Result<()> {
+        let content = match AssetSource::parse(url, None)? {
+            AssetSource::Local(folder) if folder.is_dir() => {
+                return copy_dir(
+                    &folder.join(theme.path.as_deref().unwrap_or_default()),
+                    dest,
+                );
+            }
+            AssetSource::Local(file) => fs::read(file)?,
+            AssetSource::Remote(url) => {
+                let response = get(&url)?;
+                if !response.status().is_success() {
+                    Err(UnhandledError::FailedToDownload(url))?
+                }
+                response.bytes()?.to_vec()
+            }
+        };
+        let mut zip = ZipArchive::new(Cursor::new(content))?;
+
+        let path = match &theme.path {
+            Some(path) => path.clone(),
+            None => theme_folder(&entry_names(&mut zip)?, &theme.name)
+                .ok_or(UnhandledError::InvalidSpicetifyTheme(format!(
+                    "{} has no color.ini named after {}, set the theme's path",
+                    url, theme.name
+                )))?
+                .to_string_lossy()
+                .to_string(),
+        };
+        extract_from_archive(&mut zip, url, &path, dest)
+    }
 }
 
-#[derive(Debug, Deserialize)]
-pub struct SpicetifyConfigSetting {
-    current_theme: String,
-    color_scheme: Option<String>,
+/// Folder of a `color.ini`, the one named after the theme when the archive holds several themes
+fn theme_folder(names: &[PathBuf], theme_name: &str) -> Option<PathBuf> {
+    let folders: Vec<&Path> = names
+        .iter()
+        .filter(|name| name.file_name().is_some_and(|file| file == "color.ini"))
+        .filter_map(|name| name.parent())
+        .collect();
+    let named = folders
+        .iter()
+        .find(|folder| folder.file_name().is_some_and(|name| name == theme_name));
+    match (named, folders.as_slice()) {
+        (Some(folder), _) | (None, [folder]) => Some(folder.to_path_buf()),
+        // Guessing would install another theme under this one's name
+        (None, _) => None,
+    }
 }
 
 pub struct SpicetifyThemeNotFoundError(Spicetify, ThemeSpicetify);
@@ -217,7 +473,7 @@ impl HandledError for SpicetifyThemeNotFoundError {
     fn item(&self) -> &'static str {
         "spotify theme"
     }
-    fn handle(&self) {
+    fn handle(&self, _policy: InputPolicy) {
         println!("Downloading theme...");
         match self.0.download_theme(&self.1) {
             Ok(_) => display_success(format!("Downloaded theme {}", self.1.name)),
@@ -0,0 +1,566 @@
+use crate::{
+    color::RgbaHexColor,
+    error::{Error, UnhandledError},
+    library::cache::Cache,
+    test::{serve, temp_dir},
+    theme::{
+        ThemeApp,
+        plan::PlannedAction,
+        spicetify::{
+            Spicetify, ThemeSpicetify,
+            addon::{SpicetifyAddon, SpicetifyAddonKind},
+            color::merge_color_scheme,
+            config::{SpicetifyConfig, SpicetifyOwned},
+            ini::IniDocument,
+        },
+    },
+};
+use serde_json::json;
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{Cursor, Write},
+    path::{Path, PathBuf},
+};
+use zip::{ZipWriter, write::SimpleFileOptions};
+
+/// Spicetify with its binary and its userdata in the same folder
+fn spicetify_in(dir: PathBuf) -> Spicetify {
+    Spicetify::new_with_path(dir.clone(), Spicetify::program_in(&dir))
+}
+
+#[test]
+#[cfg(unix)]
+fn test_spicetify_path_override() {
+    use crate::library::Library;
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = temp_dir("spicetify_path_override");
+    let library = Library::new_with_path(dir.join("swapeme"));
+    let spicetify = dir.join("spicetify");
+    let userdata = dir.join("userdata");
+    fs::create_dir_all(&spicetify).unwrap();
+
+    assert!(Spicetify::save_path_override(&library, spicetify.clone()).is_err());
+
+    // The binary's folder isn't where spicetify keeps its themes
+    let program = Spicetify::program_in(&spicetify);
+    fs::write(
+        &program,
+        format!(
+            "#!/bin/sh\n[ \"$1 $2\" = \"path userdata\" ] && echo '{}'\n",
+            userdata.display()
+        ),
+    )
+    .unwrap();
+    fs::set_permissions(&program, fs::Permissions::from_mode(0o755)).unwrap();
+    Spicetify::save_path_override(&library, spicetify.clone()).unwrap();
+    let settings = library.read_settings().unwrap();
+    let app = Spicetify::new_with_settings(&settings).unwrap();
+    assert_eq!(app.get_program(), program);
+    assert_eq!(app.get_owned_path(), userdata.join("swapeme.json"));
+
+    // Printing nothing means it isn't usable, instead of a panic
+    fs::write(&program, "#!/bin/sh\n").unwrap();
+    assert!(matches!(
+        Spicetify::new_with_program(program),
+        Err(Error::HandledError(_))
+    ));
+}
+
+#[test]
+fn test_spicetify_plan() {
+    let app = spicetify_in(temp_dir("plan_spicetify"));
+    let theme: ThemeSpicetify =
+        serde_json::from_str(r#"{ "name": "Cat", "color_scheme": "Mocha" }"#).unwrap();
+
+    assert_eq!(
+        theme.plan(&app).unwrap(),
+        vec![
+            PlannedAction::Other("theme Cat is not installed".to_string()),
+            PlannedAction::SetConfig {
+                key: "current_theme".to_string(),
+                value: "Cat".to_string(),
+            },
+            PlannedAction::SetConfig {
+                key: "color_scheme".to_string(),
+                value: "Mocha".to_string(),
+            },
+            PlannedAction::Command {
+                program: app.get_program(),
+                args: vec!["apply".to_string()],
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_read_spicetify_config() {
+    let config = SpicetifyConfig::parse(
+        &fs::read_to_string("resources/test/spicetify/config-xpui.ini").unwrap(),
+    )
+    .unwrap();
+    assert_eq!(config.current_theme(), "Cat");
+    assert_eq!(config.flag("inject_css"), Some(true));
+    assert_eq!(config.flag("overwrite_assets"), Some(false));
+    assert_eq!(
+        config.extensions(),
+        ["fullAppDisplay.js", "shuffle+.js", "catJam.js"]
+    );
+    assert_eq!(config.custom_apps(), ["marketplace"]);
+}
+
+#[test]
+fn test_spicetify_theme_changes() {
+    let config = SpicetifyConfig::parse(
+        &fs::read_to_string("resources/test/spicetify/config-xpui.ini").unwrap(),
+    )
+    .unwrap();
+    // catJam.js was added by the previous theme, the other extensions are the user's
+    let owned = SpicetifyOwned {
+        extensions: vec!["catJam.js".to_string()],
+        custom_apps: vec![],
+    };
+    let theme: ThemeSpicetify = serde_json::from_str(
+        r#"{
+            "name": "Cat",
+            "color_scheme": "Latte",
+            "extensions": ["shuffle+.js", "dogJam.js"],
+            "custom_apps": ["lyrics-plus"],
+            "inject_css": true,
+            "overwrite_assets": true
+        }"#,
+    )
+    .unwrap();
+
+    let changes = Spicetify::theme_changes(&theme, Some(&config), &owned);
+    assert_eq!(
+        changes.config,
+        [
+            ("color_scheme", "Latte"),
+            ("overwrite_assets", "1"),
+            ("extensions", "catJam.js-"),
+            ("extensions", "dogJam.js"),
+            ("custom_apps", "lyrics-plus"),
+        ]
+        .map(|(key, value)| (key, value.to_string()))
+    );
+    // shuffle+.js was already there, it stays the user's
+    assert_eq!(
+        changes.owned,
+        SpicetifyOwned {
+            extensions: vec!["dogJam.js".to_string()],
+            custom_apps: vec!["lyrics-plus".to_string()],
+        }
+    );
+
+    // Without a list, owned entries are left alone
+    let theme: ThemeSpicetify = serde_json::from_str(r#"{ "name": "Cat" }"#).unwrap();
+    let changes = Spicetify::theme_changes(&theme, Some(&config), &owned);
+    assert_eq!(changes.owned, owned);
+    assert!(changes.config.iter().all(|(key, _)| *key != "extensions"));
+}
+
+#[test]
+fn test_write_spicetify_config() {
+    let content = fs::read_to_string("resources/test/spicetify/config-xpui.ini").unwrap();
+    let mut config = SpicetifyConfig::parse(&content).unwrap();
+    let theme: ThemeSpicetify = serde_json::from_str(
+        r#"{
+            "name": "Cat",
+            "color_scheme": "Latte",
+            "extensions": ["dogJam.js"],
+            "custom_apps": ["lyrics-plus"],
+            "overwrite_assets": true
+        }"#,
+    )
+    .unwrap();
+    let owned = SpicetifyOwned {
+        extensions: vec!["catJam.js".to_string()],
+        custom_apps: vec![],
+    };
+    let changes = Spicetify::theme_changes(&theme, Some(&config), &owned);
+    config.set_all(&changes.config);
+
+    // Only the changed values move, comments, alignment and order stay
+    let expected = content
+        .replace(
+            "color_scheme            = Mocha",
+            "color_scheme            = Latte",
+        )
+        .replace("overwrite_assets        = 0", "overwrite_assets        = 1")
+        .replace(
+            "fullAppDisplay.js|shuffle+.js|catJam.js",
+            "fullAppDisplay.js|shuffle+.js|dogJam.js",
+        )
+        .replace("= marketplace", "= marketplace|lyrics-plus");
+    assert_eq!(config.to_string(), expected);
+
+    let config = SpicetifyConfig::parse(&config.to_string()).unwrap();
+    assert!(
+        Spicetify::theme_changes(&theme, Some(&config), &changes.owned)
+            .config
+            .is_empty()
+    );
+    assert!(SpicetifyConfig::parse("[Setting]\ncolor_scheme = Mocha\n").is_err());
+}
+
+#[test]
+#[cfg(unix)]
+fn test_spicetify_commands() {
+    use crate::error::Result;
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = temp_dir("spicetify_commands");
+    let app = spicetify_in(dir.clone());
+    // Stands in for spicetify, refresh reports its error without an exit status
+    fs::write(
+        app.get_program(),
+        "#!/bin/sh\ncase \"$1\" in\n\
+         apply) echo patched ;;\n\
+         refresh) echo 'error: theme not found' >&2 ;;\n\
+         *) echo 'unknown command' >&2; exit 1 ;;\n\
+         esac\n",
+    )
+    .unwrap();
+    fs::set_permissions(app.get_program(), fs::Permissions::from_mode(0o755)).unwrap();
+
+    app.apply().unwrap();
+    let failed = |result: Result<()>| match result {
+        Err(Error::HandledError(error)) => error.to_string(),
+        _ => panic!("spicetify didn't fail"),
+    };
+    assert_eq!(
+        failed(app.refresh()),
+        "spicetify refresh failed: error: theme not found"
+    );
+    assert_eq!(
+        failed(app.run("backup")),
+        "spicetify backup failed: unknown command"
+    );
+
+    assert!(matches!(app.read_config(), Err(Error::HandledError(_))));
+
+    // Only the theme changes on an already set up spicetify, refreshing is enough
+    fs::copy(
+        "resources/test/spicetify/config-xpui.ini",
+        dir.join("config-xpui.ini"),
+    )
+    .unwrap();
+    let theme: ThemeSpicetify =
+        serde_json::from_str(r#"{ "name": "Cat", "color_scheme": "Latte" }"#).unwrap();
+    assert_eq!(
+        theme.plan(&app).unwrap().last(),
+        Some(&PlannedAction::Command {
+            program: app.get_program(),
+            args: vec!["refresh".to_string()],
+        })
+    );
+    let theme: ThemeSpicetify =
+        serde_json::from_str(r#"{ "name": "Cat", "inject_theme_js": false }"#).unwrap();
+    assert_eq!(
+        theme.plan(&app).unwrap().last(),
+        Some(&PlannedAction::Command {
+            program: app.get_program(),
+            args: vec!["apply".to_string()],
+        })
+    );
+}
+
+#[test]
+fn test_install_spicetify_addons() {
+    let dir = temp_dir("spicetify_addons");
+    let app = spicetify_in(dir.join("spicetify"));
+    let cache = Cache::new_with_path(dir.join("cache"));
+
+    // Laid out like a GitHub archive
+    let archive = dir.join("addons.zip");
+    let mut zip = ZipWriter::new(File::create(&archive).unwrap());
+    for (name, content) in [
+        ("addons-main/dist/catJam.js", "// cat"),
+        ("addons-main/lyrics-plus/index.js", "// lyrics"),
+        ("addons-main/lyrics-plus/manifest.json", "{}"),
+    ] {
+        zip.start_file(name, SimpleFileOptions::default()).unwrap();
+        zip.write_all(content.as_bytes()).unwrap();
+    }
+    zip.finish().unwrap();
+    fs::write(dir.join("dogJam.js"), "// dog").unwrap();
+    fs::create_dir_all(dir.join("local")).unwrap();
+    fs::write(dir.join("local/birdJam.js"), "// bird").unwrap();
+
+    let mut theme: ThemeSpicetify = serde_json::from_str(
+        r#"{
+            "name": "Cat",
+            "extensions": [
+                "fullAppDisplay.js",
+                { "name": "catJam.js", "source": "addons.zip", "path": "dist/catJam.js" },
+                { "name": "dogJam.js", "source": "dogJam.js" },
+                { "name": "birdJam.js", "source": "local", "path": "birdJam.js" },
+                { "name": "../../escaped.js", "source": "dogJam.js" }
+            ],
+            "custom_apps": [{ "name": "lyrics-plus", "source": "addons.zip", "path": "lyrics-plus" }]
+        }"#,
+    )
+    .unwrap();
+    theme.resolve_assets(&dir).unwrap();
+
+    let planned = theme.plan(&app).unwrap();
+    assert!(planned.contains(&PlannedAction::Other(format!(
+        "install custom app lyrics-plus from {}",
+        archive.display()
+    ))));
+
+    assert!(planned.contains(&PlannedAction::Other(
+        "skip extension ../../escaped.js, it isn't a plain file name".to_string()
+    )));
+
+    let mut failed = vec![];
+    for (kind, addon) in theme.addons() {
+        if app.install_addon(kind, addon, &cache).is_err() {
+            failed.push((kind, addon.name().to_string()));
+        }
+    }
+    assert_eq!(
+        failed,
+        [(
+            SpicetifyAddonKind::Extension,
+            "../../escaped.js".to_string()
+        )]
+    );
+    assert!(!dir.join("escaped.js").exists());
+    assert_eq!(
+        theme
+            .without_addons(&failed)
+            .addons()
+            .map(|(_, addon)| addon.name().to_string())
+            .collect::<Vec<_>>(),
+        [
+            "fullAppDisplay.js",
+            "catJam.js",
+            "dogJam.js",
+            "birdJam.js",
+            "lyrics-plus"
+        ]
+    );
+    let extension = |name| {
+        app.get_addon_path(SpicetifyAddonKind::Extension, name)
+            .unwrap()
+    };
+    assert_eq!(
+        fs::read_to_string(extension("catJam.js")).unwrap(),
+        "// cat"
+    );
+    assert_eq!(
+        fs::read_to_string(extension("dogJam.js")).unwrap(),
+        "// dog"
+    );
+    assert_eq!(
+        fs::read_to_string(extension("birdJam.js")).unwrap(),
+        "// bird"
+    );
+    assert!(!extension("fullAppDisplay.js").exists());
+    let custom_app = app
+        .get_addon_path(SpicetifyAddonKind::CustomApp, "lyrics-plus")
+        .unwrap();
+    assert!(custom_app.join("manifest.json").is_file());
+
+    // A path missing from the archive leaves nothing behind
+    let missing = SpicetifyAddon::Source {
+        name: "missing.js".to_string(),
+        source: archive.to_string_lossy().to_string(),
+        path: Some("dist/missing.js".to_string()),
+    };
+    assert!(
+        app.install_addon(SpicetifyAddonKind::Extension, &missing, &cache)
+            .is_err()
+    );
+    assert!(!extension("missing.js").exists());
+    assert!(!extension(".missing.js.tmp").exists());
+}
+
+#[test]
+fn test_merge_spicetify_color_scheme() {
+    let content = fs::read_to_string("resources/test/spicetify/color.ini").unwrap();
+    let colors = BTreeMap::from([
+        (
+            "main".to_string(),
+            RgbaHexColor::new(0x11, 0x11, 0x1b, 0xff),
+        ),
+        (
+            "sidebar".to_string(),
+            RgbaHexColor::new(0x18, 0x18, 0x25, 0x80),
+        ),
+    ]);
+
+    // The theme's schemes are left alone, colors go over a copy of the base scheme
+    assert_eq!(
+        merge_color_scheme(&content, Some("Mocha"), "swapeme-Mocha", &colors),
+        format!(
+            "{}
+[swapeme-Mocha]
+text = cdd6f4
+subtext = a6adc8
+main = 11111B
+sidebar = 181825
+",
+            content
+        )
+    );
+
+    let merged = merge_color_scheme(&content, None, "swapeme", &colors);
+    assert!(merged.starts_with(&content));
+    let ini = IniDocument::parse(&merged);
+    assert_eq!(ini.sections(), vec!["Mocha", "Latte", "swapeme"]);
+    assert_eq!(ini.get("swapeme", "main"), Some("11111B"));
+    assert_eq!(ini.get("Mocha", "main"), Some("1e1e2e"));
+
+    // Merging again changes nothing
+    assert_eq!(
+        merge_color_scheme(&merged, None, "swapeme", &colors),
+        merged
+    );
+    assert_eq!(
+        merge_color_scheme("", None, "swapeme", &colors),
+        "[swapeme]\nmain = 11111B\nsidebar = 181825\n"
+    );
+
+    // Windows line endings are kept
+    let crlf = content.replace('\n', "\r\n");
+    let merged = merge_color_scheme(&crlf, None, "swapeme", &colors);
+    assert!(merged.starts_with(&crlf));
+    assert!(!merged.replace("\r\n", "").contains('\n'));
+}
+
+#[test]
+fn test_write_spicetify_color_scheme() {
+    let app = spicetify_in(temp_dir("spicetify_colors"));
+    let theme: ThemeSpicetify = serde_json::from_str(
+        r##"{ "name": "Cat", "colors": { "text": "#FFFFFFFF", "main": "#000000FF" } }"##,
+    )
+    .unwrap();
+    fs::create_dir_all(app.get_theme_path("Cat").unwrap()).unwrap();
+    fs::copy(
+        "resources/test/spicetify/color.ini",
+        app.get_color_path("Cat").unwrap(),
+    )
+    .unwrap();
+
+    assert_eq!(theme.scheme_name(), "swapeme");
+    let planned = theme.plan(&app).unwrap();
+    assert!(planned.contains(&PlannedAction::Other(format!(
+        "write color scheme swapeme to {}",
+        app.get_color_path("Cat").unwrap().display()
+    ))));
+    assert!(planned.contains(&PlannedAction::SetConfig {
+        key: "color_scheme".to_string(),
+        value: "swapeme".to_string(),
+    }));
+
+    app.write_color_scheme(&theme).unwrap();
+    let ini = IniDocument::parse(&fs::read_to_string(app.get_color_path("Cat").unwrap()).unwrap());
+    assert_eq!(ini.sections(), vec!["Mocha", "Latte", "swapeme"]);
+    assert_eq!(ini.get("swapeme", "text"), Some("FFFFFF"));
+
+    // Tweaking one of the theme's schemes selects swapeme's copy of it
+    let theme: ThemeSpicetify = serde_json::from_str(
+        r##"{ "name": "Cat", "color_scheme": "Mocha", "colors": { "main": "#000000FF" } }"##,
+    )
+    .unwrap();
+    assert_eq!(theme.scheme_name(), "swapeme-Mocha");
+    app.write_color_scheme(&theme).unwrap();
+    let ini = IniDocument::parse(&fs::read_to_string(app.get_color_path("Cat").unwrap()).unwrap());
+    assert_eq!(ini.get("Mocha", "main"), Some("1e1e2e"));
+    assert_eq!(ini.get("swapeme-Mocha", "main"), Some("000000"));
+    assert_eq!(ini.get("swapeme-Mocha", "text"), Some("cdd6f4"));
+}
+
+fn zip_bytes(files: &[(&str, &str)]) -> Vec<u8> {
+    let mut zip = ZipWriter::new(Cursor::new(vec![]));
+    for (name, content) in files {
+        zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+        zip.write_all(content.as_bytes()).unwrap();
+    }
+    zip.finish().unwrap().into_inner()
+}
+
+#[test]
+fn test_download_spicetify_theme() {
+    let app = spicetify_in(temp_dir("spicetify_download"));
+    // Laid out like a GitHub archive of a repo holding several themes
+    let archive = zip_bytes(&[
+        ("themes-main/README.md", "# Themes"),
+        ("themes-main/Cat/color.ini", "[Mocha]\ntext = cdd6f4\n"),
+        ("themes-main/Cat/user.css", "body {}"),
+        ("themes-main/Cat/assets/glyphs.svg", "<svg/>"),
+        ("themes-main/Dog/color.ini", "[Base]\ntext = ffffff\n"),
+    ]);
+    let url = serve(vec![
+        (200, archive.clone()),
+        (200, archive.clone()),
+        (404, vec![]),
+        (200, zip_bytes(&[("Cat/user.css", "body {}")])),
+        (200, archive),
+    ]);
+    let theme =
+        |json: serde_json::Value| -> ThemeSpicetify { serde_json::from_value(json).unwrap() };
+    let theme_path = app.get_theme_path("Cat").unwrap();
+
+    // Found by its name, with its folders
+    app.download_theme(&theme(
+        json!({ "name": "Cat", "url": format!("{}/themes.zip", url) }),
+    ))
+    .unwrap();
+    assert!(theme_path.join("user.css").is_file());
+    assert_eq!(
+        fs::read_to_string(theme_path.join("assets/glyphs.svg")).unwrap(),
+        "<svg/>"
+    );
+    assert!(!theme_path.join("README.md").exists());
+
+    // An explicit path replaces the whole install
+    app.download_theme(&theme(json!({
+        "name": "Cat",
+        "url": format!("{}/themes.zip", url),
+        "path": "Dog",
+    })))
+    .unwrap();
+    assert!(
+        fs::read_to_string(theme_path.join("color.ini"))
+            .unwrap()
+            .contains("[Base]")
+    );
+    assert!(!theme_path.join("user.css").exists());
+    assert!(!app.get_theme_path(".Cat.old").unwrap().exists());
+
+    // Failures leave the installed theme alone
+    let failing = theme(json!({ "name": "Cat", "url": format!("{}/missing.zip", url) }));
+    assert!(matches!(
+        app.download_theme(&failing),
+        Err(Error::UnhandledError(UnhandledError::FailedToDownload(_)))
+    ));
+    let without_colors = theme(json!({ "name": "Cat", "url": format!("{}/css.zip", url) }));
+    assert!(matches!(
+        app.download_theme(&without_colors),
+        Err(Error::UnhandledError(
+            UnhandledError::InvalidSpicetifyTheme(_)
+        ))
+    ));
+    assert!(theme_path.join("color.ini").is_file());
+    assert!(!app.get_theme_path(".Cat.tmp").unwrap().exists());
+
+    // Another theme of the archive isn't picked in place of a missing one
+    let unknown = theme(json!({ "name": "Bird", "url": format!("{}/themes.zip", url) }));
+    assert!(matches!(
+        app.download_theme(&unknown),
+        Err(Error::UnhandledError(
+            UnhandledError::InvalidSpicetifyTheme(_)
+        ))
+    ));
+    assert!(!app.get_theme_path("Bird").unwrap().exists());
+
+    // Names can't lead out of the themes folder
+    assert!(app.get_theme_path("../Cat").is_err());
+    let mut escaping = theme(json!({ "name": "../../Cat", "url": "themes.zip" }));
+    assert!(escaping.resolve_assets(Path::new(".")).is_err());
+}
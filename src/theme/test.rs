@@ -0,0 +1,163 @@
+use crate::{
+    test::temp_dir,
+    theme::{
+        Theme,
+        monitor::{
+            Monitor, MonitorFallback, MonitorResolver, MonitorSelector, Monitors,
+            parse_xrandr_monitors,
+        },
+        windows::wallpaper::WindowsWallpaper,
+    },
+};
+use std::{fs, rc::Rc};
+
+#[test]
+fn test_plan_skips_missing_sections() {
+    let theme = Theme::parse_json("{}").unwrap();
+    assert!(theme.plan().unwrap().sections.is_empty());
+    assert_eq!(theme.plan().unwrap().to_string(), "Nothing to apply\n");
+}
+
+#[test]
+fn test_read_file_resolves_relative_assets() {
+    let dir = temp_dir("theme_relative_assets");
+    fs::create_dir_all(dir.join("images")).unwrap();
+    fs::write(dir.join("images").join("stars.png"), "stars").unwrap();
+    let path = dir.join("stars.swapeme.json");
+    fs::write(
+        &path,
+        r#"{ "windows": { "wallpaper": { "type": "single", "url": "images/stars.png" } } }"#,
+    )
+    .unwrap();
+
+    let theme = Theme::read_file(&path).unwrap();
+    let base_dir = fs::canonicalize(&dir).unwrap();
+    assert_eq!(theme.base_dir, Some(base_dir.clone()));
+    assert!(matches!(
+        theme.windows.unwrap().wallpaper,
+        Some(WindowsWallpaper::Single { url, .. })
+            if url == base_dir.join("images").join("stars.png").to_string_lossy()
+    ));
+}
+
+fn monitor(index: u32, x: i32, width: u32, height: u32, primary: bool) -> Monitor {
+    Monitor {
+        index,
+        device: format!("\\\\.\\DISPLAY{}", index + 1),
+        x,
+        y: 0,
+        width,
+        height,
+        primary,
+    }
+}
+
+/// A 1440p primary screen with a vertical 1080p one on its left, and an ultrawide on its right
+fn three_monitors() -> Monitors {
+    Rc::new(vec![
+        monitor(0, 0, 2560, 1440, true),
+        monitor(1, -1080, 1080, 1920, false),
+        monitor(2, 2560, 3440, 1440, false),
+    ])
+}
+
+#[test]
+fn test_resolve_monitor_selectors() {
+    let resolver = MonitorResolver::from_provider(Some(&three_monitors()), MonitorFallback::Skip);
+
+    for (selector, index) in [
+        (r#"1"#, Some(1)),
+        (r#""primary""#, Some(0)),
+        (r#"{ "position": 0 }"#, Some(1)),
+        (r#"{ "position": 2 }"#, Some(2)),
+        (r#"{ "resolution": "1080x1920" }"#, Some(1)),
+        (r#"{ "aspect": "21:9" }"#, None),
+        (r#"{ "aspect": "43:18" }"#, Some(2)),
+        (r#"{ "device": "\\\\.\\display3" }"#, Some(2)),
+        (r#"5"#, None),
+        (r#"{ "position": 3 }"#, None),
+    ] {
+        let selector: MonitorSelector = serde_json::from_str(selector).unwrap();
+        assert_eq!(resolver.resolve(&selector), index, "{}", selector);
+    }
+}
+
+#[test]
+fn test_monitor_fallbacks() {
+    let missing = MonitorSelector::Index(4);
+    for (fallback, index) in [
+        (MonitorFallback::Skip, None),
+        (MonitorFallback::Primary, Some(0)),
+        (MonitorFallback::Wrap, Some(1)),
+    ] {
+        let resolver = MonitorResolver::from_provider(Some(&three_monitors()), fallback);
+        assert_eq!(resolver.resolve(&missing), index, "{:?}", fallback);
+    }
+
+    // Fallbacks landing on a monitor something else already got are reported
+    let resolver = MonitorResolver::from_provider(Some(&three_monitors()), MonitorFallback::Wrap);
+    let mut picked = vec![];
+    let first = MonitorSelector::Index(1);
+    assert!(!resolver.collides(&first, 1, &mut picked));
+    assert!(resolver.collides(&missing, 1, &mut picked));
+    assert!(!resolver.collides(&MonitorSelector::Index(2), 2, &mut picked));
+
+    // Without knowing the monitors, indexes are kept as they are
+    let resolver = MonitorResolver::from_provider(None, MonitorFallback::Skip);
+    assert_eq!(resolver.resolve(&missing), Some(4));
+    assert_eq!(
+        resolver.resolve(&MonitorSelector::Resolution {
+            resolution: "1920x1080".to_string()
+        }),
+        None
+    );
+}
+
+#[test]
+fn test_parse_xrandr_monitors() {
+    let monitors = parse_xrandr_monitors(
+        "Monitors: 2\n 0: +*DP-1 2560/597x1440/336+0+0  DP-1\n 1: +HDMI-1 1920/531x1080/299+2560+360  HDMI-1\n",
+    );
+    assert_eq!(
+        monitors,
+        vec![
+            Monitor {
+                index: 0,
+                device: "DP-1".to_string(),
+                x: 0,
+                y: 0,
+                width: 2560,
+                height: 1440,
+                primary: true,
+            },
+            Monitor {
+                index: 1,
+                device: "HDMI-1".to_string(),
+                x: 2560,
+                y: 360,
+                width: 1920,
+                height: 1080,
+                primary: false,
+            },
+        ]
+    );
+
+    // Monitors left of or above the origin have negative offsets, written either way
+    let monitors = parse_xrandr_monitors(
+        " 0: +*DP-1 2560/597x1440/336+0+0  DP-1
+ 1: +HDMI-1 1920/531x1080/299+-1920+0  HDMI-1
+ 2: +DP-2 1920/531x1080/299-1920-1080  DP-2
+",
+    );
+    assert_eq!(
+        monitors
+            .iter()
+            .map(|monitor| (monitor.device.as_str(), monitor.x, monitor.y, monitor.width))
+            .collect::<Vec<_>>(),
+        vec![
+            ("DP-1", 0, 0, 2560),
+            ("HDMI-1", -1920, 0, 1920),
+            ("DP-2", -1920, -1080, 1920)
+        ]
+    );
+}
@@ -1,4 +1,10 @@
-use crate::{error::Result, theme::wallpaper_engine::WallpaperEngine};
+use crate::{
+    error::Result,
+    theme::wallpaper_engine::{
+        FullscreenBehavior, PlaylistMode, PlaylistOrder, Wallpaper, WallpaperEngine,
+        WallpaperLayout, error::UserNotFoundError,
+    },
+};
 use core::fmt;
 use serde::{
     Deserialize, Deserializer, Serialize, Serializer,
@@ -6,13 +12,120 @@ use serde::{
 };
 use serde_json::Value;
 use std::path::PathBuf;
-use std::{collections::HashMap, fs};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+};
 
 impl WallpaperEngine {
+    pub fn get_config_path(&self) -> PathBuf {
+        self.path.join("config.json")
+    }
+
     pub fn read_config(&self) -> Result<WallpaperEngineConfig> {
-        let content = fs::read_to_string(&self.path.join("config.json"))?;
+        let content = fs::read_to_string(self.get_config_path())?;
         Ok(serde_json::from_str(&content)?)
     }
+
+    /// The user Wallpaper Engine keeps this session's settings under
+    pub fn get_current_user(&self) -> Result<String> {
+        Ok(WallpaperEngineConfig::get_current_user().ok_or(UserNotFoundError(self.clone()))?)
+    }
+
+    /// Only written to `config.json`, which Wallpaper Engine reads when it starts
+    pub fn set_layout(&self, layout: WallpaperLayout) -> Result<()> {
+        self.set_layout_for_user(&self.get_current_user()?, layout)
+    }
+
+    pub fn set_layout_for_user(&self, user: &str, layout: WallpaperLayout) -> Result<()> {
+        self.set_user_setting(
+            user,
+            &["general", "wallpaperconfig", "layout"],
+            layout.to_config().into(),
+        )
+    }
+
+    pub fn get_fullscreen(&self) -> Result<Option<FullscreenBehavior>> {
+        let config = self.read_config()?;
+        Ok(config
+            .get_current_user_config()
+            .ok_or(UserNotFoundError(self.clone()))?
+            .general
+            .fullscreen)
+    }
+
+    /// Only written to `config.json`, which Wallpaper Engine reads when it starts
+    pub fn set_fullscreen(&self, fullscreen: FullscreenBehavior) -> Result<()> {
+        self.set_fullscreen_for_user(&self.get_current_user()?, fullscreen)
+    }
+
+    pub fn set_fullscreen_for_user(
+        &self,
+        user: &str,
+        fullscreen: FullscreenBehavior,
+    ) -> Result<()> {
+        self.set_user_setting(
+            user,
+            &["general", "fullscreen"],
+            serde_json::to_value(fullscreen)?,
+        )
+    }
+
+    /// Saves the playlist under its name, `openPlaylist` only opens saved playlists
+    pub fn save_playlist_for_user(
+        &self,
+        user: &str,
+        playlist: &WallpaperEnginePlaylist,
+    ) -> Result<()> {
+        let value = serde_json::to_value(playlist)?;
+        self.edit_user_config(user, |config| {
+            let playlists = &mut config["general"]["playlists"];
+            if !playlists.is_array() {
+                *playlists = Value::Array(vec![]);
+            }
+            let playlists = playlists.as_array_mut().unwrap();
+            match playlists
+                .iter_mut()
+                .find(|saved| saved["name"] == playlist.name.as_str())
+            {
+                Some(saved) => merge_json(saved, value),
+                None => playlists.push(value),
+            }
+        })
+    }
+
+    fn set_user_setting(&self, user: &str, keys: &[&str], value: Value) -> Result<()> {
+        self.edit_user_config(user, |config| {
+            let mut setting = config;
+            for key in keys {
+                setting = &mut setting[*key];
+            }
+            *setting = value;
+        })
+    }
+
+    /// Edits `config.json` in place, leaving everything swapeme doesn't know about untouched and in order
+    fn edit_user_config(&self, user: &str, edit: impl FnOnce(&mut Value)) -> Result<()> {
+        let path = self.get_config_path();
+        let mut config: Value = serde_json::from_str(&fs::read_to_string(&path)?)?;
+        let Some(user_config) = config.get_mut(user) else {
+            Err(UserNotFoundError(self.clone()))?
+        };
+        edit(user_config);
+        Ok(fs::write(path, serde_json::to_string_pretty(&config)?)?)
+    }
+}
+
+/// Overwrites what `value` sets, keeping the other keys of `target`'s objects
+fn merge_json(target: &mut Value, value: Value) {
+    match (target, value) {
+        (Value::Object(target), Value::Object(value)) => {
+            for (key, value) in value {
+                merge_json(target.entry(key).or_insert(Value::Null), value);
+            }
+        }
+        (target, value) => *target = value,
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -24,21 +137,58 @@ pub struct WallpaperEngineConfig {
 }
 
 impl WallpaperEngineConfig {
+    /// Wallpaper Engine keys its settings by the name of the user's home folder
+    pub fn get_current_user() -> Option<String> {
+        Some(
+            dirs::desktop_dir()?
+                .parent()?
+                .file_name()?
+                .to_str()?
+                .to_string(),
+        )
+    }
+
     pub fn get_current_user_config(&self) -> Option<&WallpaperEngineUserConfig> {
-        self.users
-            .get(dirs::desktop_dir()?.parent()?.file_name()?.to_str()?)
+        self.users.get(&Self::get_current_user()?)
     }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct WallpaperEngineUserConfig {
     pub general: WallpaperEngineUserGeneralConfig,
+    /// Properties the user changed, by `project.json` path
+    #[serde(rename = "wproperties", default)]
+    pub wallpaper_properties: HashMap<PathBuf, BTreeMap<String, Value>>,
+}
+
+impl WallpaperEngineUserConfig {
+    pub fn get_wallpaper_properties(
+        &self,
+        wallpaper: &Wallpaper,
+    ) -> Option<&BTreeMap<String, Value>> {
+        // Paths aren't always written the same way, compare what they point to instead.
+        // Keys left behind by wallpapers that moved or were deleted don't point to any
+        self.wallpaper_properties
+            .iter()
+            .find(|(path, _)| Wallpaper::from_path(path).as_ref() == Some(wallpaper))
+            .map(|(_, properties)| properties)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct WallpaperEngineUserGeneralConfig {
     #[serde(rename = "wallpaperconfig")]
     pub wallpaper_config: Option<UserWallpaperConfig>,
+    /// Playlists saved in Wallpaper Engine, `openPlaylist` only knows about these
+    #[serde(default)]
+    pub playlists: Vec<WallpaperEnginePlaylist>,
+    pub fullscreen: Option<FullscreenBehavior>,
+}
+
+impl WallpaperEngineUserGeneralConfig {
+    pub fn get_playlist(&self, name: &str) -> Option<&WallpaperEnginePlaylist> {
+        self.playlists.iter().find(|playlist| playlist.name == name)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -97,5 +247,42 @@ impl<'de> Deserialize<'de> for UserWallpaperConfigMonitorId {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UserWallpaperConfigForMonitor {
     pub file: PathBuf,
-    pub playlist: Option<Value>,
+    pub playlist: Option<WallpaperEnginePlaylist>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WallpaperEnginePlaylist {
+    pub name: String,
+    /// `project.json` of every wallpaper in the playlist
+    pub items: Vec<PathBuf>,
+    pub settings: WallpaperEnginePlaylistSettings,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WallpaperEnginePlaylistSettings {
+    /// Minutes between two wallpapers in timer mode
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delay: Option<u32>,
+    #[serde(default)]
+    pub mode: PlaylistMode,
+    #[serde(default)]
+    pub order: PlaylistOrder,
+    #[serde(default)]
+    pub transition: bool,
+}
+
+impl WallpaperEnginePlaylist {
+    /// Same wallpapers and settings, however their paths are written
+    pub fn same_as(&self, other: &WallpaperEnginePlaylist) -> bool {
+        let wallpapers = |playlist: &WallpaperEnginePlaylist| {
+            playlist
+                .items
+                .iter()
+                .map(|item| Wallpaper::from_path(item))
+                .collect::<Vec<_>>()
+        };
+        self.name == other.name
+            && self.settings == other.settings
+            && wallpapers(self) == wallpapers(other)
+    }
 }
@@ -0,0 +1,146 @@
+use crate::{
+    error::{Error, Result},
+    theme::{
+        plan::PlannedAction,
+        wallpaper_engine::{
+            WallpaperEngine,
+            error::{CommandFailedError, InstallationNotFoundError},
+        },
+    },
+};
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    fmt::Debug,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    process::Command,
+    rc::Rc,
+};
+
+/// Runs programs, faked in tests to check the exact invocations
+pub trait CommandRunner {
+    fn run(&self, program: &Path, args: &[String]) -> Result<()>;
+}
+
+pub type Runner = Rc<dyn CommandRunner>;
+
+impl Debug for dyn CommandRunner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("CommandRunner")
+    }
+}
+
+/// Runs programs for real and waits for them
+pub struct ProcessRunner;
+
+impl CommandRunner for ProcessRunner {
+    fn run(&self, program: &Path, args: &[String]) -> Result<()> {
+        let output = Command::new(program)
+            .args(args)
+            .output()
+            .map_err(|err| match err.kind() {
+                ErrorKind::NotFound => InstallationNotFoundError {}.into(),
+                _ => Error::from(err),
+            })?;
+        if !output.status.success() {
+            Err(CommandFailedError {
+                command: args.join(" "),
+                status: output.status,
+                stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            })?
+        }
+        Ok(())
+    }
+}
+
+/// A command of Wallpaper Engine's `-control` CLI, `None` monitors mean every monitor
+#[derive(Debug, Clone, PartialEq)]
+pub enum WallpaperCommand {
+    OpenWallpaper {
+        /// `project.json` of the wallpaper
+        file: PathBuf,
+        monitor: Option<u32>,
+    },
+    /// Only works with playlists saved in Wallpaper Engine
+    OpenPlaylist {
+        name: String,
+        monitor: Option<u32>,
+    },
+    ApplyProperties {
+        properties: BTreeMap<String, Value>,
+        monitor: Option<u32>,
+    },
+    CloseWallpaper {
+        monitor: Option<u32>,
+    },
+    Play,
+    Pause,
+    Stop,
+    Mute,
+    Unmute,
+    HideIcons,
+    ShowIcons,
+}
+
+impl WallpaperCommand {
+    /// Arguments given to Wallpaper Engine, starting with `-control`
+    pub fn args(&self) -> Vec<String> {
+        let (name, mut args, monitor) = match self {
+            WallpaperCommand::OpenWallpaper { file, monitor } => (
+                "openWallpaper",
+                vec!["-file".to_string(), file.to_string_lossy().to_string()],
+                monitor,
+            ),
+            WallpaperCommand::OpenPlaylist { name, monitor } => (
+                "openPlaylist",
+                vec!["-playlist".to_string(), name.clone()],
+                monitor,
+            ),
+            WallpaperCommand::ApplyProperties {
+                properties,
+                monitor,
+            } => (
+                "applyProperties",
+                vec![
+                    "-properties".to_string(),
+                    format!("RAW~({})~END", serde_json::to_string(properties).unwrap()),
+                ],
+                monitor,
+            ),
+            WallpaperCommand::CloseWallpaper { monitor } => ("closeWallpaper", vec![], monitor),
+            WallpaperCommand::Play => ("play", vec![], &None),
+            WallpaperCommand::Pause => ("pause", vec![], &None),
+            WallpaperCommand::Stop => ("stop", vec![], &None),
+            WallpaperCommand::Mute => ("mute", vec![], &None),
+            WallpaperCommand::Unmute => ("unmute", vec![], &None),
+            WallpaperCommand::HideIcons => ("hideIcons", vec![], &None),
+            WallpaperCommand::ShowIcons => ("showIcons", vec![], &None),
+        };
+
+        if let Some(monitor) = monitor {
+            args.push("-monitor".to_string());
+            args.push(monitor.to_string());
+        }
+        [vec!["-control".to_string(), name.to_string()], args].concat()
+    }
+}
+
+impl WallpaperEngine {
+    pub fn with_runner(mut self, runner: Runner) -> Self {
+        self.runner = runner;
+        self
+    }
+
+    pub fn control(&self, command: &WallpaperCommand) -> Result<()> {
+        self.runner.run(&self.get_app32_path(), &command.args())
+    }
+
+    /// What [`WallpaperEngine::control`] would run
+    pub fn plan_control(&self, command: &WallpaperCommand) -> PlannedAction {
+        PlannedAction::Command {
+            program: self.get_app32_path(),
+            args: command.args(),
+        }
+    }
+}
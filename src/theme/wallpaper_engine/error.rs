@@ -1,7 +1,13 @@
-use crate::cli::wait_for_user;
+use crate::cli::{InputPolicy, display_error, display_success, wait_for_user};
 use crate::error::HandledError;
-use crate::theme::wallpaper_engine::{Wallpaper, WallpaperEngine};
+use crate::library::Library;
+use crate::theme::wallpaper_engine::linux::LinuxWallpaperEngine;
+use crate::theme::wallpaper_engine::workshop::{WorkshopItem, WorkshopItemStatus, human_size};
+use crate::theme::wallpaper_engine::{Wallpaper, WallpaperEngine, WallpaperKind};
+use dialoguer::Input;
 use std::fmt::Display;
+use std::path::PathBuf;
+use std::process::ExitStatus;
 
 pub struct WallpaperNotFoundError(pub Wallpaper, pub WallpaperEngine);
 
@@ -9,8 +15,7 @@ impl Display for WallpaperNotFoundError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_str(&format!(
             "Wallpaper {} from {} not found",
-            self.0.id,
-            self.0.kind.to_string(),
+            self.0.id, self.0.kind,
         ))
     }
 }
@@ -23,10 +28,127 @@ impl HandledError for WallpaperNotFoundError {
         "this wallpaper"
     }
 
-    fn handle(&self) {
-        self.1.open_workshop_page_for_wallpaper(&self.0.id).unwrap();
+    fn handle(&self, policy: InputPolicy) {
+        self.1.open_workshop_page_for_wallpaper(&self.0.id, policy);
 
-        wait_for_user("wallpaper installation");
+        wait_for_user("wallpaper installation", policy);
+    }
+}
+
+/// Every wallpaper of a theme that isn't installed, reported at once
+pub struct MissingWallpapersError(
+    pub Vec<(Wallpaper, Option<WorkshopItem>)>,
+    pub WallpaperEngine,
+);
+
+impl Display for MissingWallpapersError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} wallpapers aren't installed", self.0.len())?;
+        for (wallpaper, item) in &self.0 {
+            write!(f, "\n  {} from {}", wallpaper.id, wallpaper.kind)?;
+            match (wallpaper.kind, item) {
+                (WallpaperKind::Workshop, Some(item)) => write!(f, ", {}", item.status())?,
+                (WallpaperKind::Workshop, None) => {
+                    write!(f, ", {}", WorkshopItemStatus::NotSubscribed)?
+                }
+                _ => {}
+            }
+            if let Some(size) = item.as_ref().and_then(|item| item.size) {
+                write!(f, ", {}", human_size(size))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl HandledError for MissingWallpapersError {
+    fn action(&self) -> &'static str {
+        "Install them from workshop"
+    }
+
+    fn item(&self) -> &'static str {
+        "missing wallpapers"
+    }
+
+    fn handle(&self, policy: InputPolicy) {
+        // Subscribed ones only need steam to catch up
+        for (wallpaper, item) in &self.0 {
+            let subscribed = item.as_ref().is_some_and(|item| item.subscribed);
+            if wallpaper.kind == WallpaperKind::Workshop && !subscribed {
+                self.1
+                    .open_workshop_page_for_wallpaper(&wallpaper.id, policy);
+            }
+        }
+
+        wait_for_user("wallpapers installation", policy);
+    }
+}
+
+/// A `-control` command Wallpaper Engine rejected
+pub struct CommandFailedError {
+    pub command: String,
+    pub status: ExitStatus,
+    pub stderr: String,
+}
+
+impl Display for CommandFailedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "wallpaper engine {} failed, {}",
+            self.command, self.status
+        )?;
+        if !self.stderr.is_empty() {
+            write!(f, ": {}", self.stderr)?;
+        }
+        Ok(())
+    }
+}
+
+impl HandledError for CommandFailedError {
+    fn item(&self) -> &'static str {
+        "wallpaper engine step"
+    }
+
+    fn action(&self) -> &'static str {
+        "Retry it"
+    }
+
+    fn handle(&self, policy: InputPolicy) {
+        if policy.is_interactive() {
+            println!("Wallpaper engine has to be running to take commands");
+        }
+        wait_for_user("wallpaper engine", policy);
+    }
+}
+
+/// Wallpaper Engine adds users to its config the first time they run it
+pub struct UserNotFoundError(pub WallpaperEngine);
+
+impl Display for UserNotFoundError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Couldn't find your user in {}",
+            self.0.get_config_path().display()
+        )
+    }
+}
+
+impl HandledError for UserNotFoundError {
+    fn item(&self) -> &'static str {
+        "wallpaper engine step"
+    }
+
+    fn action(&self) -> &'static str {
+        "Start wallpaper engine once"
+    }
+
+    fn handle(&self, policy: InputPolicy) {
+        if policy.is_interactive() {
+            println!("Wallpaper engine saves your settings once it has started");
+        }
+        wait_for_user("wallpaper engine", policy);
     }
 }
 
@@ -47,7 +169,72 @@ impl HandledError for InstallationNotFoundError {
         "Set wallpaper engine's path"
     }
 
-    fn handle(&self) {
-        todo!();
+    fn handle(&self, policy: InputPolicy) {
+        let Ok(library) = Library::new() else {
+            return display_error("Couldn't find where to store settings");
+        };
+        if !policy.is_interactive() {
+            return display_error(format!(
+                "Set paths.wallpaper_engine or paths.steam in {}",
+                library.get_settings_path().display()
+            ));
+        }
+
+        let path: String = Input::new()
+            .with_prompt("Path to wallpaper engine's or steam's folder")
+            .interact_text()
+            .unwrap();
+        match WallpaperEngine::save_path_override(
+            &library,
+            PathBuf::from(path.trim().trim_matches('"')),
+        ) {
+            Ok(_) => display_success("Saved wallpaper engine's path"),
+            Err(_) => display_error(format!(
+                "{} contains neither wallpaper32.exe nor steamapps",
+                path.trim()
+            )),
+        }
+    }
+}
+
+pub struct RendererNotFoundError();
+
+impl Display for RendererNotFoundError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Couldn't find linux-wallpaperengine on your system")
+    }
+}
+
+impl HandledError for RendererNotFoundError {
+    fn item(&self) -> &'static str {
+        "wallpaper engine step"
+    }
+
+    fn action(&self) -> &'static str {
+        "Set linux-wallpaperengine's path"
+    }
+
+    fn handle(&self, policy: InputPolicy) {
+        let Ok(library) = Library::new() else {
+            return display_error("Couldn't find where to store settings");
+        };
+        if !policy.is_interactive() {
+            return display_error(format!(
+                "Install linux-wallpaperengine or set paths.linux_wallpaperengine in {}",
+                library.get_settings_path().display()
+            ));
+        }
+
+        let path: String = Input::new()
+            .with_prompt("Path to the linux-wallpaperengine executable")
+            .interact_text()
+            .unwrap();
+        match LinuxWallpaperEngine::save_path_override(
+            &library,
+            PathBuf::from(path.trim().trim_matches('"')),
+        ) {
+            Ok(_) => display_success("Saved linux-wallpaperengine's path"),
+            Err(_) => display_error(format!("{} isn't a file", path.trim())),
+        }
     }
 }
@@ -0,0 +1,383 @@
+use crate::{
+    error::{Result, UnhandledError},
+    library::{Library, settings::Settings},
+    theme::{
+        monitor::{
+            Monitor, MonitorFallback, MonitorResolver, MonitorSelector, Monitors, get_monitors,
+        },
+        wallpaper_engine::{
+            FullscreenBehavior, ThemeWEPlayback, ThemeWEWallpaper, ThemeWallpaperEngine, Wallpaper,
+            WallpaperEngine, WallpaperKind,
+            error::{RendererNotFoundError, WallpaperNotFoundError},
+        },
+    },
+};
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+/// Renders Wallpaper Engine's wallpapers on Linux with linux-wallpaperengine, one process per output
+#[derive(Clone)]
+pub struct LinuxWallpaperEngine {
+    pub binary: PathBuf,
+    /// Wallpaper Engine's installation running under Proton, for its assets and wallpapers
+    pub app: WallpaperEngine,
+    pub monitors: Option<Monitors>,
+}
+
+/// A wallpaper a running linux-wallpaperengine process renders on an output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinuxWallpaperProcess {
+    pub pid: u32,
+    pub output: String,
+    /// Wallpaper folder or workshop id, as given to `--bg`
+    pub background: String,
+    pub properties: BTreeMap<String, String>,
+    /// The whole command line of the process, without the program
+    pub args: Vec<String>,
+}
+
+impl LinuxWallpaperEngine {
+    pub const BINARY: &str = "linux-wallpaperengine";
+
+    pub fn new() -> Result<Self> {
+        Self::new_with_settings(&Settings::load())
+    }
+
+    /// Uses the path set in the settings before looking in `PATH`
+    pub fn new_with_settings(settings: &Settings) -> Result<Self> {
+        let app = WallpaperEngine::new_with_settings(settings)?;
+        let binary = settings
+            .paths
+            .linux_wallpaperengine
+            .clone()
+            .filter(|path| path.is_file())
+            .or_else(|| {
+                env::split_paths(&env::var_os("PATH")?)
+                    .map(|dir| dir.join(Self::BINARY))
+                    .find(|path| path.is_file())
+            })
+            .ok_or(RendererNotFoundError())?;
+
+        Ok(Self {
+            binary,
+            app,
+            monitors: get_monitors(),
+        })
+    }
+
+    pub fn save_path_override(library: &Library, path: PathBuf) -> Result<()> {
+        if !path.is_file() {
+            Err(UnhandledError::InvalidAppPath(path.clone()))?
+        }
+        let mut settings = library.read_settings()?;
+        settings.paths.linux_wallpaperengine = Some(path);
+        library.write_settings(&settings)
+    }
+
+    pub fn outputs(&self) -> Vec<Monitor> {
+        self.monitors
+            .as_ref()
+            .and_then(|monitors| monitors.get_monitors().ok())
+            .unwrap_or_default()
+    }
+
+    /// Name of the output a selector points to, like `DP-1`
+    pub fn resolve_output(
+        &self,
+        resolver: &MonitorResolver,
+        selector: &MonitorSelector,
+    ) -> Option<String> {
+        let index = resolver.resolve(selector)?;
+        self.outputs()
+            .into_iter()
+            .find(|monitor| monitor.index == index)
+            .map(|monitor| monitor.device)
+    }
+
+    /// Arguments rendering a wallpaper with its properties on an output
+    pub fn wallpaper_args(
+        &self,
+        wallpaper: &ThemeWEWallpaper,
+        output: &str,
+        playback: Option<&ThemeWEPlayback>,
+    ) -> Vec<String> {
+        let mut args = vec![];
+        let assets = self.app.path.join("assets");
+        if assets.is_dir() {
+            args.push("--assets-dir".to_string());
+            args.push(assets.to_string_lossy().to_string());
+        }
+
+        let folder = self.app.get_wallpaper_path(&wallpaper.wallpaper);
+        args.extend([
+            "--screen-root".to_string(),
+            output.to_string(),
+            "--bg".to_string(),
+            folder.parent().unwrap().to_string_lossy().to_string(),
+        ]);
+
+        for (name, value) in wallpaper.properties.iter().flatten() {
+            let value = match value {
+                Value::String(value) => value.clone(),
+                value => value.to_string(),
+            };
+            args.push("--set-property".to_string());
+            args.push(format!("{}={}", name, value));
+        }
+        args.extend(
+            playback
+                .map(ThemeWEPlayback::linux_args)
+                .unwrap_or_default(),
+        );
+        args
+    }
+
+    /// Replaces whatever was rendered on the output
+    pub fn set_wallpaper(
+        &self,
+        wallpaper: &ThemeWEWallpaper,
+        output: &str,
+        playback: Option<&ThemeWEPlayback>,
+    ) -> Result<()> {
+        if !self.app.get_wallpaper_path(&wallpaper.wallpaper).exists() {
+            return Err(
+                WallpaperNotFoundError(wallpaper.wallpaper.clone(), self.app.clone()).into(),
+            );
+        }
+        let wallpaper = ThemeWEWallpaper {
+            properties: self.app.checked_properties(wallpaper).cloned(),
+            ..wallpaper.clone()
+        };
+
+        self.stop(output)?;
+        self.spawn(&self.wallpaper_args(&wallpaper, output, playback))
+    }
+
+    /// Starts linux-wallpaperengine, which keeps running after swapeme exits
+    fn spawn(&self, args: &[String]) -> Result<()> {
+        Command::new(&self.binary)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        Ok(())
+    }
+
+    /// Kills the processes rendering on the output, then starts again what they rendered on other outputs
+    pub fn stop(&self, output: &str) -> Result<()> {
+        let mut killed = vec![];
+        for process in Self::running() {
+            if process.output != output || killed.contains(&process.pid) {
+                continue;
+            }
+            Command::new("kill")
+                .arg(process.pid.to_string())
+                .stderr(Stdio::null())
+                .status()?;
+            killed.push(process.pid);
+
+            if let Some(args) = process.args_without_output() {
+                self.spawn(&args)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Wallpapers rendered by linux-wallpaperengine right now, read from `/proc`
+    pub fn running() -> Vec<LinuxWallpaperProcess> {
+        let Ok(entries) = fs::read_dir("/proc") else {
+            return vec![];
+        };
+
+        let mut processes = vec![];
+        for entry in entries.flatten() {
+            let Some(pid) = entry.file_name().to_str().and_then(|pid| pid.parse().ok()) else {
+                continue;
+            };
+            let Ok(cmdline) = fs::read(entry.path().join("cmdline")) else {
+                continue;
+            };
+            let args: Vec<String> = cmdline
+                .split(|byte| *byte == 0)
+                .filter(|arg| !arg.is_empty())
+                .map(|arg| String::from_utf8_lossy(arg).to_string())
+                .collect();
+            if args.first().is_some_and(|program| {
+                Path::new(program).file_name() == Some(Self::BINARY.as_ref())
+            }) {
+                processes.extend(LinuxWallpaperProcess::parse(pid, &args[1..]));
+            }
+        }
+        processes.sort_by(|a, b| a.output.cmp(&b.output));
+        processes
+    }
+
+    pub fn get_current(&self) -> ThemeWallpaperEngine {
+        let monitors =
+            MonitorResolver::from_provider(self.monitors.as_ref(), MonitorFallback::default());
+        let wallpapers: Vec<_> = Self::running()
+            .iter()
+            .filter_map(|process| process.to_theme_wallpaper(&monitors))
+            .collect();
+        ThemeWallpaperEngine {
+            layout: None,
+            wallpapers: (!wallpapers.is_empty()).then_some(wallpapers),
+            playlist: None,
+            monitor_fallback: None,
+            playback: None,
+        }
+    }
+}
+
+impl ThemeWEPlayback {
+    /// linux-wallpaperengine's flags for the settings it supports
+    pub fn linux_args(&self) -> Vec<String> {
+        let mut args = vec![];
+        if self.muted == Some(true) {
+            args.push("--silent".to_string());
+        }
+        if self.fullscreen == Some(FullscreenBehavior::Run) {
+            args.push("--no-fullscreen-pause".to_string());
+        }
+        args
+    }
+
+    /// Whether some settings can't be given to linux-wallpaperengine
+    pub fn has_linux_unsupported(&self) -> bool {
+        self.paused.is_some()
+            || self.hide_icons.is_some()
+            || matches!(
+                self.fullscreen,
+                Some(FullscreenBehavior::Mute | FullscreenBehavior::Stop)
+            )
+    }
+}
+
+impl LinuxWallpaperProcess {
+    /// Reads the `--screen-root <output> --bg <wallpaper>` pairs of a command line
+    pub fn parse(pid: u32, args: &[String]) -> Vec<Self> {
+        let mut processes: Vec<Self> = vec![];
+        let mut properties = BTreeMap::new();
+        let mut output = None;
+        let command_line = args.to_vec();
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--screen-root" => output = args.next().cloned(),
+                "--bg" => {
+                    if let (Some(output), Some(background)) = (output.take(), args.next()) {
+                        processes.push(Self {
+                            pid,
+                            output,
+                            background: background.clone(),
+                            properties: BTreeMap::new(),
+                            args: vec![],
+                        });
+                    }
+                }
+                "--set-property" => {
+                    if let Some((name, value)) = args.next().and_then(|p| p.split_once('=')) {
+                        properties.insert(name.to_string(), value.to_string());
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // Properties apply to every wallpaper of the process
+        for process in &mut processes {
+            process.properties = properties.clone();
+            process.args = command_line.clone();
+        }
+        processes
+    }
+
+    /// The command line rendering the same as this process, except on this output.
+    /// `None` when the process doesn't render anything else
+    pub fn args_without_output(&self) -> Option<Vec<String>> {
+        let mut remaining = vec![];
+        let mut other_outputs = false;
+        // Set between this output's `--screen-root` and its `--bg`
+        let mut dropping = false;
+
+        let mut args = self.args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--screen-root" => {
+                    let Some(output) = args.next() else { break };
+                    dropping = *output == self.output;
+                    if !dropping {
+                        other_outputs = true;
+                        remaining.extend([arg.clone(), output.clone()]);
+                    }
+                }
+                "--bg" if dropping => {
+                    args.next();
+                    dropping = false;
+                }
+                _ => remaining.push(arg.clone()),
+            }
+        }
+        other_outputs.then_some(remaining)
+    }
+
+    /// The wallpaper a `--bg` refers to, `None` for folders outside Wallpaper Engine's
+    pub fn wallpaper(&self) -> Option<Wallpaper> {
+        if self.background.chars().all(|c| c.is_ascii_digit()) {
+            return Some(Wallpaper {
+                id: self.background.clone(),
+                kind: WallpaperKind::Workshop,
+            });
+        }
+
+        let path = Path::new(&self.background);
+        let kind_dir = path.parent()?;
+        let workshop = Path::new("workshop")
+            .join("content")
+            .join(WallpaperEngine::STEAM_GAME_ID);
+        let kind = if kind_dir.ends_with(workshop) {
+            WallpaperKind::Workshop
+        } else if kind_dir.parent()?.file_name()? == "projects" {
+            serde_json::from_value(Value::String(kind_dir.file_name()?.to_str()?.to_string()))
+                .ok()
+                .filter(|kind| *kind != WallpaperKind::Workshop)?
+        } else {
+            return None;
+        };
+        Some(Wallpaper {
+            id: path.file_name()?.to_str()?.to_string(),
+            kind,
+        })
+    }
+
+    /// Points to the output by its position, output names rarely match between machines
+    pub fn to_theme_wallpaper(&self, monitors: &MonitorResolver) -> Option<ThemeWEWallpaper> {
+        let properties: BTreeMap<String, Value> = self
+            .properties
+            .iter()
+            .map(|(name, value)| {
+                let value = serde_json::from_str(value).unwrap_or(Value::String(value.clone()));
+                (name.clone(), value)
+            })
+            .collect();
+
+        Some(ThemeWEWallpaper {
+            wallpaper: self.wallpaper()?,
+            monitor: monitors
+                .position_of(&self.output)
+                .map(|position| MonitorSelector::Position { position })
+                // Without xrandr there's nothing else to go by
+                .unwrap_or(MonitorSelector::Device {
+                    device: self.output.clone(),
+                }),
+            properties: (!properties.is_empty()).then_some(properties),
+        })
+    }
+}
@@ -1,52 +1,96 @@
 use super::ThemeApp;
-use crate::cli::start_cmd;
-use crate::error::{Error, Result};
-use crate::theme::wallpaper_engine::config::WallpaperEngineUserConfig;
-use crate::theme::wallpaper_engine::error::{InstallationNotFoundError, WallpaperNotFoundError};
+use super::plan::PlannedAction;
+use crate::cli::{InputPolicy, display_error, open_for_user};
+use crate::error::{Error, Result, UnhandledError};
+use crate::library::{Library, settings::Settings};
+use crate::theme::monitor::{
+    MonitorFallback, MonitorResolver, MonitorSelector, Monitors, get_monitors,
+};
+use crate::theme::wallpaper_engine::config::{
+    WallpaperEnginePlaylist, WallpaperEnginePlaylistSettings, WallpaperEngineUserConfig,
+};
+use crate::theme::wallpaper_engine::control::{ProcessRunner, Runner, WallpaperCommand};
+use crate::theme::wallpaper_engine::error::{
+    MissingWallpapersError, UserNotFoundError, WallpaperNotFoundError,
+};
+use crate::theme::wallpaper_engine::linux::LinuxWallpaperEngine;
+use crate::theme::wallpaper_engine::steam::SteamDiscovery;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use serde_variant::to_variant_name;
+use std::collections::BTreeMap;
 use std::fmt::Display;
-use std::fs;
 use std::path::{Path, PathBuf};
-use std::process::Command;
-use vdf_parser::VdfValue;
-use vdf_parser::error::VdfError;
-use vdf_parser::parse_vdf_text;
-use winreg::RegKey;
-use winreg::enums::HKEY_LOCAL_MACHINE;
+use std::rc::Rc;
 
 pub mod config;
+pub mod control;
 pub mod error;
+pub mod linux;
+pub mod project;
+pub mod steam;
+#[cfg(test)]
+mod test;
+pub mod workshop;
 
 impl ThemeApp for ThemeWallpaperEngine {
     const NAME: &'static str = "Wallpaper engine";
-    type App = WallpaperEngine;
+    type App = WallpaperEngineBackend;
 
-    fn apply(&self, app: WallpaperEngine) -> Result<()> {
-        if let Some(wallpapers) = &self.wallpapers {
-            for wallpaper in wallpapers {
-                Error::error_prone_step(
-                    &|| {
-                        app.set_wallpaper(&wallpaper.wallpaper, wallpaper.monitor)
-                            .map_err(|e| e.into())
-                    },
-                    Some(&format!(
-                        "Applied wallpaper {} from {} on monitor {}",
-                        wallpaper.wallpaper.id, wallpaper.wallpaper.kind, wallpaper.monitor
-                    )),
-                );
-            }
+    fn apply(&self, app: WallpaperEngineBackend) -> Result<()> {
+        match app {
+            WallpaperEngineBackend::Native(app) => self.apply_native(app),
+            WallpaperEngineBackend::Linux(linux) => self.apply_linux(linux),
         }
+    }
 
-        Ok(())
+    fn plan(&self, app: &WallpaperEngineBackend) -> Result<Vec<PlannedAction>> {
+        match app {
+            WallpaperEngineBackend::Native(app) => self.plan_native(app),
+            WallpaperEngineBackend::Linux(linux) => Ok(self.plan_linux(linux)),
+        }
     }
 
-    fn get_current(app: WallpaperEngine) -> Result<Self> {
-        Ok((app.read_config()?.get_current_user_config().unwrap()).into())
+    fn get_current(app: WallpaperEngineBackend) -> Result<Self> {
+        match app {
+            WallpaperEngineBackend::Native(app) => {
+                let config = app.read_config()?;
+                let user = config
+                    .get_current_user_config()
+                    .ok_or(UserNotFoundError(app.clone()))?;
+                Ok(user.into())
+            }
+            WallpaperEngineBackend::Linux(linux) => Ok(linux.get_current()),
+        }
+    }
+    fn get_app() -> Result<Option<Self::App>> {
+        Error::error_prone_step(&|| WallpaperEngineBackend::new(), None)
+    }
+}
+
+/// What renders the wallpapers, the theme itself doesn't depend on it
+#[derive(Clone)]
+pub enum WallpaperEngineBackend {
+    /// Wallpaper Engine itself, controlled with `-control`
+    Native(WallpaperEngine),
+    /// linux-wallpaperengine, rendering the same wallpapers on Linux
+    Linux(LinuxWallpaperEngine),
+}
+
+impl WallpaperEngineBackend {
+    pub fn new() -> Result<Self> {
+        if cfg!(target_os = "linux") {
+            Ok(Self::Linux(LinuxWallpaperEngine::new()?))
+        } else {
+            Ok(Self::Native(WallpaperEngine::new()?))
+        }
     }
-    fn get_app() -> Option<Self::App> {
-        Error::error_prone_step(&|| WallpaperEngine::new().map_err(|e| (e).into()), None)
+}
+
+impl From<WallpaperEngine> for WallpaperEngineBackend {
+    fn from(app: WallpaperEngine) -> Self {
+        Self::Native(app)
     }
 }
 
@@ -58,73 +102,626 @@ pub struct ThemeAuthor {
 
 #[derive(Debug, Deserialize, Serialize, JsonSchema)]
 pub struct ThemeWallpaperEngine {
+    /// How wallpapers are spread over the monitors, set before any wallpaper
+    pub layout: Option<WallpaperLayout>,
     pub wallpapers: Option<Vec<ThemeWEWallpaper>>,
     pub playlist: Option<Vec<ThemeWEPlaylist>>,
+    /// Where wallpapers for monitors this machine doesn't have go
+    pub monitor_fallback: Option<MonitorFallback>,
+    /// Set once every wallpaper is opened
+    pub playback: Option<ThemeWEPlayback>,
 }
 
-#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+impl ThemeWallpaperEngine {
+    /// One line per wallpaper and playlist, with titles when they're installed
+    pub fn describe(&self, app: &WallpaperEngine) -> Vec<String> {
+        let wallpapers = self.wallpapers.iter().flatten().map(|wallpaper| {
+            format!(
+                "{} on {}",
+                app.describe_wallpaper(&wallpaper.wallpaper),
+                wallpaper.monitor
+            )
+        });
+        let playlists = self.playlist.iter().flatten().map(|playlist| {
+            format!(
+                "playlist {} of {} wallpapers on {}",
+                playlist.name,
+                playlist.items.len(),
+                playlist.monitor
+            )
+        });
+        wallpapers.chain(playlists).collect()
+    }
+
+    /// Wallpapers and playlist items
+    pub fn all_wallpapers(&self) -> impl Iterator<Item = &Wallpaper> {
+        let wallpapers = self.wallpapers.iter().flatten().map(|w| &w.wallpaper);
+        let items = self.playlist.iter().flatten().flat_map(|p| &p.items);
+        wallpapers.chain(items)
+    }
+
+    pub fn monitor_resolver(&self, monitors: Option<&Monitors>) -> MonitorResolver {
+        MonitorResolver::from_provider(monitors, self.monitor_fallback.unwrap_or_default())
+    }
+
+    fn apply_native(&self, app: WallpaperEngine) -> Result<()> {
+        // Wallpapers are laid out according to the layout, so it has to come first
+        if let Some(layout) = self.layout
+            && app.get_layout().ok() != Some(layout)
+        {
+            Error::error_prone_step(
+                &|| app.set_layout(layout),
+                Some(&format!(
+                    "Set wallpaper engine's layout to {}, restart wallpaper engine to apply it",
+                    layout
+                )),
+            )?;
+        }
+
+        let missing = self.report_missing_wallpapers(&app)?;
+        let monitors = self.monitor_resolver(get_monitors().as_ref());
+        let mut picked = vec![];
+
+        for wallpaper in self.wallpapers.iter().flatten() {
+            let Some(monitor) = monitors.resolve(&wallpaper.monitor) else {
+                display_error(format!(
+                    "No {} found, skipped wallpaper {}",
+                    wallpaper.monitor, wallpaper.wallpaper.id
+                ));
+                continue;
+            };
+            if monitors.collides(&wallpaper.monitor, monitor, &mut picked) {
+                display_error(format!(
+                    "Wallpaper {} lands on monitor {} along with another one through the monitor fallback",
+                    wallpaper.wallpaper.id, monitor
+                ));
+            }
+            if missing.contains(&wallpaper.wallpaper) {
+                display_error(format!(
+                    "Wallpaper {} isn't installed, skipped it",
+                    wallpaper.wallpaper.id
+                ));
+                continue;
+            }
+            Error::error_prone_step(
+                &|| app.set_theme_wallpaper(wallpaper, monitor),
+                Some(&format!(
+                    "Applied wallpaper {} on monitor {}",
+                    app.describe_wallpaper(&wallpaper.wallpaper),
+                    monitor
+                )),
+            )?;
+        }
+
+        for playlist in self.playlist.iter().flatten() {
+            let Some(monitor) = monitors.resolve(&playlist.monitor) else {
+                display_error(format!(
+                    "No {} found, skipped playlist {}",
+                    playlist.monitor, playlist.name
+                ));
+                continue;
+            };
+            if monitors.collides(&playlist.monitor, monitor, &mut picked) {
+                display_error(format!(
+                    "Playlist {} lands on monitor {} along with another wallpaper through the monitor fallback",
+                    playlist.name, monitor
+                ));
+            }
+            if playlist.items.iter().any(|item| missing.contains(item)) {
+                display_error(format!(
+                    "Some wallpapers of playlist {} aren't installed, skipped it",
+                    playlist.name
+                ));
+                continue;
+            }
+            Error::error_prone_step(
+                &|| app.set_playlist(playlist, monitor),
+                Some(&format!(
+                    "Applied playlist {} on monitor {}",
+                    playlist.name, monitor
+                )),
+            )?;
+        }
+
+        if let Some(playback) = &self.playback {
+            if let Some(fullscreen) = playback.fullscreen
+                && app.get_fullscreen().ok().flatten() != Some(fullscreen)
+            {
+                Error::error_prone_step(
+                    &|| app.set_fullscreen(fullscreen),
+                    Some(&format!(
+                        "Set wallpapers to {} while an app is fullscreen, restart wallpaper engine to apply it",
+                        fullscreen
+                    )),
+                )?;
+            }
+            for command in playback.commands() {
+                Error::error_prone_step(
+                    &|| app.control(&command),
+                    Some(&format!("Sent {} to wallpaper engine", command.args()[1])),
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn plan_native(&self, app: &WallpaperEngine) -> Result<Vec<PlannedAction>> {
+        let monitors = self.monitor_resolver(get_monitors().as_ref());
+        let mut actions = vec![];
+        if let Some(layout) = self.layout
+            && app.get_layout().ok() != Some(layout)
+        {
+            actions.push(PlannedAction::SetConfig {
+                key: "layout".to_string(),
+                value: layout.to_string(),
+            });
+            actions.push(PlannedAction::Other(
+                "restart wallpaper engine to apply the layout".to_string(),
+            ));
+        }
+        actions.extend(self.plan_missing_wallpapers(app));
+        let mut picked = vec![];
+
+        for wallpaper in self.wallpapers.iter().flatten() {
+            let Some(monitor) = monitors.resolve(&wallpaper.monitor) else {
+                actions.push(PlannedAction::Other(format!(
+                    "no {} found, wallpaper {} would be skipped",
+                    wallpaper.monitor, wallpaper.wallpaper.id
+                )));
+                continue;
+            };
+            if monitors.collides(&wallpaper.monitor, monitor, &mut picked) {
+                actions.push(PlannedAction::Other(format!(
+                    "wallpaper {} would land on monitor {} along with another one through the monitor fallback",
+                    wallpaper.wallpaper.id, monitor
+                )));
+            }
+            actions.push(app.plan_control(&WallpaperCommand::OpenWallpaper {
+                file: app.get_wallpaper_path(&wallpaper.wallpaper),
+                monitor: Some(monitor),
+            }));
+
+            if let Some(properties) = &wallpaper.properties {
+                if let Ok(project) = app.read_project(&wallpaper.wallpaper)
+                    && let Err(err) = project.validate_properties(properties)
+                {
+                    actions.push(PlannedAction::Other(format!(
+                        "properties of wallpaper {} are invalid and would be skipped: {:?}",
+                        wallpaper.wallpaper.id, err
+                    )));
+                    continue;
+                }
+
+                actions.push(app.plan_control(&WallpaperCommand::ApplyProperties {
+                    properties: properties.clone(),
+                    monitor: Some(monitor),
+                }));
+            }
+        }
+
+        let config = app.read_config().ok();
+        let saved_playlist = |name: &str| {
+            config
+                .as_ref()
+                .and_then(|config| config.get_current_user_config())
+                .and_then(|user| user.general.get_playlist(name))
+        };
+        for playlist in self.playlist.iter().flatten() {
+            let Some(monitor) = monitors.resolve(&playlist.monitor) else {
+                actions.push(PlannedAction::Other(format!(
+                    "no {} found, playlist {} would be skipped",
+                    playlist.monitor, playlist.name
+                )));
+                continue;
+            };
+            if monitors.collides(&playlist.monitor, monitor, &mut picked) {
+                actions.push(PlannedAction::Other(format!(
+                    "playlist {} would land on monitor {} along with another wallpaper through the monitor fallback",
+                    playlist.name, monitor
+                )));
+            }
+            if !saved_playlist(&playlist.name)
+                .is_some_and(|saved| saved.same_as(&playlist.to_config(app)))
+            {
+                actions.push(PlannedAction::Other(format!(
+                    "save playlist {} in Wallpaper Engine's config",
+                    playlist.name
+                )));
+            }
+
+            actions.push(app.plan_control(&WallpaperCommand::OpenPlaylist {
+                name: playlist.name.clone(),
+                monitor: Some(monitor),
+            }));
+        }
+
+        if let Some(playback) = &self.playback {
+            if let Some(fullscreen) = playback.fullscreen
+                && app.get_fullscreen().ok().flatten() != Some(fullscreen)
+            {
+                actions.push(PlannedAction::SetConfig {
+                    key: "fullscreen".to_string(),
+                    value: fullscreen.to_string(),
+                });
+                actions.push(PlannedAction::Other(
+                    "restart wallpaper engine to apply the fullscreen behavior".to_string(),
+                ));
+            }
+            for command in playback.commands() {
+                actions.push(app.plan_control(&command));
+            }
+        }
+        Ok(actions)
+    }
+
+    fn apply_linux(&self, linux: LinuxWallpaperEngine) -> Result<()> {
+        if let Some(layout) = self.layout.filter(|l| *l != WallpaperLayout::PerMonitor) {
+            display_error(format!(
+                "linux-wallpaperengine renders each monitor on its own, ignored layout {}",
+                layout
+            ));
+        }
+        let missing = self.report_missing_wallpapers(&linux.app)?;
+        let monitors = self.monitor_resolver(linux.monitors.as_ref());
+        let mut picked = vec![];
+
+        let wallpapers = if self.wallpapers.is_some() && linux.outputs().is_empty() {
+            display_error(
+                "No outputs found, linux-wallpaperengine needs xrandr to know them, skipped the wallpapers",
+            );
+            None
+        } else {
+            self.wallpapers.as_ref()
+        };
+        for wallpaper in wallpapers.into_iter().flatten() {
+            let Some(output) = linux.resolve_output(&monitors, &wallpaper.monitor) else {
+                display_error(format!(
+                    "No {} found, skipped wallpaper {}",
+                    wallpaper.monitor, wallpaper.wallpaper.id
+                ));
+                continue;
+            };
+            if monitors.collides(&wallpaper.monitor, output.clone(), &mut picked) {
+                display_error(format!(
+                    "Wallpaper {} lands on {} along with another one through the monitor fallback",
+                    wallpaper.wallpaper.id, output
+                ));
+            }
+            if missing.contains(&wallpaper.wallpaper) {
+                display_error(format!(
+                    "Wallpaper {} isn't installed, skipped it",
+                    wallpaper.wallpaper.id
+                ));
+                continue;
+            }
+            Error::error_prone_step(
+                &|| linux.set_wallpaper(wallpaper, &output, self.playback.as_ref()),
+                Some(&format!(
+                    "Applied wallpaper {} on {}",
+                    linux.app.describe_wallpaper(&wallpaper.wallpaper),
+                    output
+                )),
+            )?;
+        }
+
+        for playlist in self.playlist.iter().flatten() {
+            display_error(format!(
+                "linux-wallpaperengine can't play playlists, skipped playlist {}",
+                playlist.name
+            ));
+        }
+        if self
+            .playback
+            .as_ref()
+            .is_some_and(ThemeWEPlayback::has_linux_unsupported)
+        {
+            display_error(
+                "linux-wallpaperengine only supports muting and running fullscreen, ignored the other playback settings",
+            );
+        }
+        Ok(())
+    }
+
+    fn plan_linux(&self, linux: &LinuxWallpaperEngine) -> Vec<PlannedAction> {
+        let monitors = self.monitor_resolver(linux.monitors.as_ref());
+        let running = LinuxWallpaperEngine::running();
+        let mut actions = vec![];
+        if let Some(layout) = self.layout.filter(|l| *l != WallpaperLayout::PerMonitor) {
+            actions.push(PlannedAction::Other(format!(
+                "layout {} would be ignored, linux-wallpaperengine renders each monitor on its own",
+                layout
+            )));
+        }
+        actions.extend(self.plan_missing_wallpapers(&linux.app));
+        let mut picked = vec![];
+
+        let wallpapers = if self.wallpapers.is_some() && linux.outputs().is_empty() {
+            actions.push(PlannedAction::Other(
+                "no outputs found, linux-wallpaperengine needs xrandr to know them, the wallpapers would be skipped"
+                    .to_string(),
+            ));
+            None
+        } else {
+            self.wallpapers.as_ref()
+        };
+        for wallpaper in wallpapers.into_iter().flatten() {
+            let Some(output) = linux.resolve_output(&monitors, &wallpaper.monitor) else {
+                actions.push(PlannedAction::Other(format!(
+                    "no {} found, wallpaper {} would be skipped",
+                    wallpaper.monitor, wallpaper.wallpaper.id
+                )));
+                continue;
+            };
+            if monitors.collides(&wallpaper.monitor, output.clone(), &mut picked) {
+                actions.push(PlannedAction::Other(format!(
+                    "wallpaper {} would land on {} along with another one through the monitor fallback",
+                    wallpaper.wallpaper.id, output
+                )));
+            }
+            for process in running.iter().filter(|p| p.output == output) {
+                actions.push(PlannedAction::Other(format!(
+                    "stop linux-wallpaperengine rendering {} on {} (pid {})",
+                    process.background, output, process.pid
+                )));
+                if let Some(args) = process.args_without_output() {
+                    actions.push(PlannedAction::Command {
+                        program: linux.binary.clone(),
+                        args,
+                    });
+                }
+            }
+            actions.push(PlannedAction::Command {
+                program: linux.binary.clone(),
+                args: linux.wallpaper_args(wallpaper, &output, self.playback.as_ref()),
+            });
+        }
+
+        for playlist in self.playlist.iter().flatten() {
+            actions.push(PlannedAction::Other(format!(
+                "linux-wallpaperengine can't play playlists, playlist {} would be skipped",
+                playlist.name
+            )));
+        }
+        if self
+            .playback
+            .as_ref()
+            .is_some_and(ThemeWEPlayback::has_linux_unsupported)
+        {
+            actions.push(PlannedAction::Other(
+                "playback settings other than muted and running fullscreen would be ignored"
+                    .to_string(),
+            ));
+        }
+        actions
+    }
+
+    /// Reports every missing wallpaper at once, returns those still missing afterwards
+    fn report_missing_wallpapers(&self, app: &WallpaperEngine) -> Result<Vec<Wallpaper>> {
+        Error::error_prone_step(
+            &|| {
+                let missing = app.find_missing_wallpapers(self.all_wallpapers());
+                if missing.is_empty() {
+                    Ok(())
+                } else {
+                    Err(MissingWallpapersError(missing, app.clone()).into())
+                }
+            },
+            None,
+        )?;
+        Ok(app
+            .find_missing_wallpapers(self.all_wallpapers())
+            .into_iter()
+            .map(|(wallpaper, _)| wallpaper)
+            .collect())
+    }
+
+    fn plan_missing_wallpapers(&self, app: &WallpaperEngine) -> Vec<PlannedAction> {
+        app.find_missing_wallpapers(self.all_wallpapers())
+            .into_iter()
+            .map(|(wallpaper, item)| {
+                PlannedAction::Other(match item {
+                    Some(item) => format!(
+                        "wallpaper {} from {} is not installed, {}",
+                        wallpaper.id,
+                        wallpaper.kind,
+                        item.status()
+                    ),
+                    None => format!(
+                        "wallpaper {} from {} is not installed and would have to be installed first",
+                        wallpaper.id, wallpaper.kind
+                    ),
+                })
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+pub struct ThemeWEPlayback {
+    pub muted: Option<bool>,
+    pub paused: Option<bool>,
+    pub fullscreen: Option<FullscreenBehavior>,
+    pub hide_icons: Option<bool>,
+}
+
+impl ThemeWEPlayback {
+    /// Control commands for everything but the fullscreen behavior, which lives in the config
+    pub fn commands(&self) -> Vec<WallpaperCommand> {
+        let mut commands = vec![];
+        if let Some(muted) = self.muted {
+            commands.push(if muted {
+                WallpaperCommand::Mute
+            } else {
+                WallpaperCommand::Unmute
+            });
+        }
+        if let Some(paused) = self.paused {
+            commands.push(if paused {
+                WallpaperCommand::Pause
+            } else {
+                WallpaperCommand::Play
+            });
+        }
+        if let Some(hide_icons) = self.hide_icons {
+            commands.push(if hide_icons {
+                WallpaperCommand::HideIcons
+            } else {
+                WallpaperCommand::ShowIcons
+            });
+        }
+        commands
+    }
+}
+
+/// What wallpapers do while another app is fullscreen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum FullscreenBehavior {
+    /// Keeps playing
+    Run,
+    Mute,
+    Pause,
+    /// Unloads the wallpaper until the app leaves fullscreen
+    Stop,
+}
+
+impl Display for FullscreenBehavior {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(to_variant_name(self).unwrap())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 pub struct ThemeWEPlaylist {
-    //TODO
-    pub monitor: u32,
+    /// Name of the playlist in Wallpaper Engine
+    pub name: String,
+    pub items: Vec<Wallpaper>,
+    #[serde(default)]
+    pub order: PlaylistOrder,
+    #[serde(default)]
+    pub mode: PlaylistMode,
+    /// Minutes between two wallpapers in timer mode
+    pub delay: Option<u32>,
+    #[serde(default)]
+    pub transition: bool,
+    pub monitor: MonitorSelector,
 }
 
-#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum WallpaperLayout {
+    /// A wallpaper for each monitor
+    PerMonitor,
+    /// One wallpaper stretched across every monitor
+    Span,
+    /// The same wallpaper on every monitor
+    Clone,
+}
+
+impl WallpaperLayout {
+    /// Value of `layout` in Wallpaper Engine's `config.json`
+    pub fn to_config(self) -> u32 {
+        match self {
+            WallpaperLayout::PerMonitor => 0,
+            WallpaperLayout::Span => 1,
+            WallpaperLayout::Clone => 2,
+        }
+    }
+
+    pub fn from_config(value: u32) -> Self {
+        match value {
+            1 => WallpaperLayout::Span,
+            2 => WallpaperLayout::Clone,
+            _ => WallpaperLayout::PerMonitor,
+        }
+    }
+}
+
+impl Display for WallpaperLayout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(to_variant_name(self).unwrap())
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum PlaylistOrder {
+    #[default]
+    Sequential,
+    Random,
+}
+
+/// When Wallpaper Engine switches to the next wallpaper
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum PlaylistMode {
+    #[default]
+    Timer,
+    /// Spreads the wallpapers over the day
+    Daytime,
+    /// On every log in
+    Logon,
+    Never,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 pub struct ThemeWEWallpaper {
     #[serde(flatten)]
     pub wallpaper: Wallpaper,
-    pub monitor: u32,
+    pub monitor: MonitorSelector,
+    /// Values for the wallpaper's user properties, as named in its `project.json`
+    pub properties: Option<BTreeMap<String, Value>>,
 }
 
 #[derive(Debug, Clone)]
 pub struct WallpaperEngine {
     pub path: PathBuf,
+    pub runner: Runner,
 }
 
 impl WallpaperEngine {
     pub const STEAM_GAME_ID: &str = "431960";
 
-    fn get_steam_path() -> Result<String> {
-        let hklm = RegKey::predef(HKEY_LOCAL_MACHINE);
-        let steam_key = hklm
-            .open_subkey("SOFTWARE\\WOW6432Node\\Valve\\Steam")
-            .map_err(|_| InstallationNotFoundError {})?;
-        let path: String = steam_key.get_value("InstallPath")?;
-        Ok(path)
+    pub fn new_with_path(path: PathBuf) -> Self {
+        Self {
+            path,
+            runner: Rc::new(ProcessRunner),
+        }
     }
 
-    fn get_wallpaper_engine_path(steam_path: &str) -> Result<String> {
-        let vdf_path = format!("{}/steamapps/libraryfolders.vdf", steam_path);
-        let content = fs::read_to_string(&vdf_path)?;
-        let data = parse_vdf_text(&content)?;
-
-        let VdfValue::Block(libraryfolders) = &data.value else {
-            return Err(
-                VdfError::ValueNotFound("libraryfolders".to_string(), "".to_string()).into(),
-            );
-        };
+    pub fn new() -> Result<Self> {
+        Self::new_with_settings(&Settings::load())
+    }
 
-        for (_, lib) in libraryfolders {
-            let VdfValue::String(path) = &lib.get_string_value("path")?.value else {
-                continue;
-            };
-            let VdfValue::Block(app_list) = &lib.get_string_value("apps")?.value else {
-                continue;
-            };
-            if app_list.contains_key(Self::STEAM_GAME_ID) {
-                return Ok(format!("{}/steamapps/common/wallpaper_engine", path));
-            }
+    /// Uses the paths set in the settings before looking for steam
+    pub fn new_with_settings(settings: &Settings) -> Result<Self> {
+        if let Some(path) = &settings.paths.wallpaper_engine
+            && Self::is_installation(path)
+        {
+            return Ok(Self::new_with_path(path.clone()));
         }
-        Err(InstallationNotFoundError {}.into())
+
+        Ok(Self::new_with_path(
+            SteamDiscovery::system(settings).find_app(Self::STEAM_GAME_ID)?,
+        ))
     }
 
-    pub fn new_with_path(path: PathBuf) -> Self {
-        Self { path }
+    pub fn is_installation(path: &Path) -> bool {
+        path.join("wallpaper32.exe").is_file()
     }
 
-    pub fn new() -> Result<Self> {
-        Ok(Self::new_with_path(PathBuf::from(
-            Self::get_wallpaper_engine_path(&Self::get_steam_path()?)?,
-        )))
+    /// Saves a path given by the user as either wallpaper engine's or steam's folder
+    pub fn save_path_override(library: &Library, path: PathBuf) -> Result<()> {
+        let mut settings = library.read_settings()?;
+        if Self::is_installation(&path) {
+            settings.paths.wallpaper_engine = Some(path);
+        } else if path.join("steamapps").join("libraryfolders.vdf").is_file() {
+            settings.paths.steam = Some(path);
+        } else {
+            Err(UnhandledError::InvalidAppPath(path))?
+        }
+        library.write_settings(&settings)
     }
 
     pub fn get_app32_path(&self) -> PathBuf {
@@ -154,53 +751,88 @@ impl WallpaperEngine {
             .join("project.json")
     }
 
-    pub fn invoke_command(&self) -> Command {
-        let app_path = self.get_app32_path();
-        let mut command = Command::new(app_path);
-        command.arg("-control");
-        command
-    }
-
-    pub fn set_wallpaper(&self, wallpaper: &Wallpaper, monitor: u32) -> Result<()> {
-        let wallpaper_path = match &wallpaper.kind {
+    pub fn get_wallpaper_path(&self, wallpaper: &Wallpaper) -> PathBuf {
+        match &wallpaper.kind {
             WallpaperKind::Workshop => self.get_workshop_wallpaper_path(&wallpaper.id),
             other => self.get_local_wallpaper_path(&wallpaper.id, &other.to_string()),
-        };
+        }
+    }
 
-        if !wallpaper_path.exists() {
+    pub fn set_wallpaper(&self, wallpaper: &Wallpaper, monitor: u32) -> Result<()> {
+        if !self.get_wallpaper_path(wallpaper).exists() {
             return Err(WallpaperNotFoundError(wallpaper.clone(), self.clone()).into());
         }
 
-        let process = self
-            .invoke_command()
-            .args([
-                "openWallpaper",
-                "-file",
-                wallpaper_path.to_str().unwrap(),
-                "-monitor",
-                &monitor.to_string(),
-            ])
-            .spawn()?;
+        self.control(&WallpaperCommand::OpenWallpaper {
+            file: self.get_wallpaper_path(wallpaper),
+            monitor: Some(monitor),
+        })
+    }
 
-        let output = process.wait_with_output()?;
-        output
-            .status
-            .success()
-            .then_some(())
-            .ok_or(InstallationNotFoundError {}.into())
+    pub fn get_layout(&self) -> Result<WallpaperLayout> {
+        let config = self.read_config()?;
+        let layout = config
+            .get_current_user_config()
+            .and_then(|user| user.general.wallpaper_config.as_ref())
+            .ok_or(UserNotFoundError(self.clone()))?
+            .layout;
+        Ok(WallpaperLayout::from_config(layout))
     }
 
-    pub fn open_workshop_page_for_wallpaper(&self, wallpaper_id: &str) -> Result<()> {
-        start_cmd(&format!("steam://url/CommunityFilePage/{}", wallpaper_id))?;
+    /// Opens the wallpaper, then overrides its properties once they're validated
+    pub fn set_theme_wallpaper(&self, wallpaper: &ThemeWEWallpaper, monitor: u32) -> Result<()> {
+        self.set_wallpaper(&wallpaper.wallpaper, monitor)?;
+
+        if let Some(properties) = self.checked_properties(wallpaper) {
+            self.control(&WallpaperCommand::ApplyProperties {
+                properties: properties.clone(),
+                monitor: Some(monitor),
+            })?;
+        }
         Ok(())
     }
+
+    /// Saves the theme's playlist in Wallpaper Engine, then opens it
+    pub fn set_playlist(&self, playlist: &ThemeWEPlaylist, monitor: u32) -> Result<()> {
+        for wallpaper in &playlist.items {
+            if !self.get_wallpaper_path(wallpaper).exists() {
+                return Err(WallpaperNotFoundError(wallpaper.clone(), self.clone()).into());
+            }
+        }
+
+        let wanted = playlist.to_config(self);
+        let config = self.read_config()?;
+        let saved = config
+            .get_current_user_config()
+            .and_then(|user| user.general.get_playlist(&playlist.name));
+        if !saved.is_some_and(|saved| saved.same_as(&wanted)) {
+            if saved.is_some() {
+                display_error(format!(
+                    "Playlist {} saved in wallpaper engine differs from the theme's, replacing it",
+                    playlist.name
+                ));
+            }
+            self.save_playlist_for_user(&self.get_current_user()?, &wanted)?;
+        }
+
+        self.control(&WallpaperCommand::OpenPlaylist {
+            name: playlist.name.clone(),
+            monitor: Some(monitor),
+        })
+    }
+
+    pub fn open_workshop_page_for_wallpaper(&self, wallpaper_id: &str, policy: InputPolicy) {
+        open_for_user(
+            &format!("steam://url/CommunityFilePage/{}", wallpaper_id),
+            policy,
+        );
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema, Clone)]
 pub struct Wallpaper {
     pub id: String,
     pub kind: WallpaperKind,
-    // pub config: TODO
 }
 
 #[derive(Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema, Clone, Copy)]
@@ -218,69 +850,112 @@ impl Display for WallpaperKind {
 }
 
 impl From<&WallpaperEngineUserConfig> for ThemeWallpaperEngine {
-    fn from(value: &WallpaperEngineUserConfig) -> Self {
+    fn from(user: &WallpaperEngineUserConfig) -> Self {
         let mut wallpapers = vec![];
         let mut playlists = vec![];
 
-        for (key, value) in value
+        // A user that never picked a wallpaper has no wallpaper config yet
+        for (key, value) in user
             .general
             .wallpaper_config
-            .as_ref()
-            .expect("Couldn't find wallpaper config")
-            .selected_wallpapers
             .iter()
+            .flat_map(|config| &config.selected_wallpapers)
         {
             if let Some(playlist) = &value.playlist {
-                playlists.push(ThemeWEPlaylist { monitor: key.0 });
-                todo!();
-            } else {
+                playlists.push(ThemeWEPlaylist::from_config(playlist, key.0));
+            } else if let Some(wallpaper) = Wallpaper::from_config_path(&value.file) {
                 let wallpaper = ThemeWEWallpaper {
-                    wallpaper: value.file.as_path().into(),
-                    monitor: key.0,
+                    properties: user.get_wallpaper_properties(&wallpaper).cloned(),
+                    wallpaper,
+                    monitor: MonitorSelector::Index(key.0),
                 };
                 wallpapers.push(wallpaper);
             }
         }
 
         ThemeWallpaperEngine {
-            wallpapers: (wallpapers.len() != 0).then_some(wallpapers),
-            playlist: (playlists.len() != 0).then_some(playlists),
+            layout: user
+                .general
+                .wallpaper_config
+                .as_ref()
+                .map(|config| WallpaperLayout::from_config(config.layout)),
+            wallpapers: (!wallpapers.is_empty()).then_some(wallpapers),
+            playlist: (!playlists.is_empty()).then_some(playlists),
+            monitor_fallback: None,
+            playback: user.general.fullscreen.map(|fullscreen| ThemeWEPlayback {
+                fullscreen: Some(fullscreen),
+                ..Default::default()
+            }),
         }
     }
 }
 
-impl From<&Path> for Wallpaper {
-    fn from(value: &Path) -> Self {
-        let wallpaper_dir = value.parent().unwrap();
-        Wallpaper {
-            id: wallpaper_dir
-                .file_name()
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .to_string(),
-            kind: wallpaper_dir.parent().unwrap().into(),
+impl ThemeWEPlaylist {
+    pub fn from_config(playlist: &WallpaperEnginePlaylist, monitor: u32) -> Self {
+        ThemeWEPlaylist {
+            name: playlist.name.clone(),
+            items: playlist
+                .items
+                .iter()
+                .filter_map(|item| Wallpaper::from_config_path(item))
+                .collect(),
+            order: playlist.settings.order,
+            mode: playlist.settings.mode,
+            delay: playlist.settings.delay,
+            transition: playlist.settings.transition,
+            monitor: MonitorSelector::Index(monitor),
+        }
+    }
+
+    /// How Wallpaper Engine saves it, with the paths of this installation
+    pub fn to_config(&self, app: &WallpaperEngine) -> WallpaperEnginePlaylist {
+        WallpaperEnginePlaylist {
+            name: self.name.clone(),
+            items: self
+                .items
+                .iter()
+                .map(|item| app.get_wallpaper_path(item))
+                .collect(),
+            settings: WallpaperEnginePlaylistSettings {
+                delay: self.delay,
+                mode: self.mode,
+                order: self.order,
+                transition: self.transition,
+            },
         }
     }
 }
 
-impl From<&Path> for WallpaperKind {
-    fn from(value: &Path) -> Self {
-        let path_str = value.to_str().unwrap().to_string();
-        if path_str.contains("workshop") {
-            WallpaperKind::Workshop
+impl Wallpaper {
+    /// The wallpaper a `project.json` path points to, `None` outside of Wallpaper Engine's folders
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let wallpaper_dir = path.parent()?;
+        Some(Wallpaper {
+            id: wallpaper_dir.file_name()?.to_str()?.to_string(),
+            kind: WallpaperKind::from_dir(wallpaper_dir.parent()?)?,
+        })
+    }
+
+    /// Like [`Wallpaper::from_path`], but says which path was skipped
+    fn from_config_path(path: &Path) -> Option<Self> {
+        let wallpaper = Self::from_path(path);
+        if wallpaper.is_none() {
+            display_error(format!(
+                "Skipped {}, it isn't a wallpaper engine wallpaper",
+                path.display()
+            ));
+        }
+        wallpaper
+    }
+}
+
+impl WallpaperKind {
+    /// The kind of the wallpapers in `dir`, `None` when it isn't one of Wallpaper Engine's folders
+    pub fn from_dir(dir: &Path) -> Option<Self> {
+        if dir.to_str()?.contains("workshop") {
+            Some(WallpaperKind::Workshop)
         } else {
-            serde_json::from_str(&format!(
-                "\"{}\"",
-                value
-                    .parent()
-                    .unwrap()
-                    .file_name()
-                    .unwrap()
-                    .to_str()
-                    .unwrap(),
-            ))
-            .unwrap()
+            serde_json::from_value(Value::String(dir.file_name()?.to_str()?.to_string())).ok()
         }
     }
 }
@@ -0,0 +1,194 @@
+use crate::{
+    cli::display_error,
+    error::{Result, UnhandledError},
+    theme::wallpaper_engine::{ThemeWEWallpaper, Wallpaper, WallpaperEngine, WallpaperKind},
+};
+use serde::Deserialize;
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    fs,
+};
+
+/// A wallpaper's `project.json`
+#[derive(Debug, Deserialize)]
+pub struct WallpaperProject {
+    pub title: Option<String>,
+    #[serde(rename = "type", default)]
+    pub kind: WallpaperType,
+    /// Image or gif next to `project.json`
+    pub preview: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(rename = "contentrating")]
+    pub content_rating: Option<String>,
+    pub general: Option<WallpaperProjectGeneral>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WallpaperType {
+    #[serde(alias = "Scene")]
+    Scene,
+    #[serde(alias = "Video")]
+    Video,
+    #[serde(alias = "Web")]
+    Web,
+    #[serde(alias = "Application")]
+    Application,
+    #[default]
+    #[serde(other)]
+    Unknown,
+}
+
+impl Display for WallpaperType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            WallpaperType::Scene => "scene",
+            WallpaperType::Video => "video",
+            WallpaperType::Web => "web",
+            WallpaperType::Application => "application",
+            WallpaperType::Unknown => "unknown",
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WallpaperProjectGeneral {
+    #[serde(default)]
+    pub properties: HashMap<String, WallpaperProjectProperty>,
+}
+
+/// A user property as declared by the wallpaper, with its default value
+#[derive(Debug, Deserialize)]
+pub struct WallpaperProjectProperty {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub value: Option<Value>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    #[serde(default)]
+    pub options: Vec<WallpaperProjectPropertyOption>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WallpaperProjectPropertyOption {
+    pub label: Option<String>,
+    pub value: Value,
+}
+
+impl WallpaperEngine {
+    pub fn read_project(&self, wallpaper: &Wallpaper) -> Result<WallpaperProject> {
+        let content = fs::read_to_string(self.get_wallpaper_path(wallpaper))?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// The theme's property overrides, `None` once reported if the wallpaper doesn't accept them
+    pub fn checked_properties<'a>(
+        &self,
+        wallpaper: &'a ThemeWEWallpaper,
+    ) -> Option<&'a BTreeMap<String, Value>> {
+        let properties = wallpaper.properties.as_ref().filter(|p| !p.is_empty())?;
+        let checked = self
+            .read_project(&wallpaper.wallpaper)
+            .and_then(|project| project.validate_properties(properties));
+        match checked {
+            Ok(()) => Some(properties),
+            Err(err) => {
+                display_error(format!(
+                    "Skipped the properties of wallpaper {}: {:?}",
+                    wallpaper.wallpaper.id, err
+                ));
+                None
+            }
+        }
+    }
+
+    /// Title and id of the wallpaper, just the id if it isn't installed
+    pub fn describe_wallpaper(&self, wallpaper: &Wallpaper) -> String {
+        match self.read_project(wallpaper).ok().and_then(|p| p.title) {
+            Some(title) => format!("{} ({})", title, wallpaper.id),
+            None => format!("{} from {}", wallpaper.id, wallpaper.kind),
+        }
+    }
+
+    /// Every installed wallpaper, from the workshop and from local projects, sorted by id
+    pub fn list_wallpapers(&self) -> Result<Vec<(Wallpaper, WallpaperProject)>> {
+        let mut wallpapers = vec![];
+        for kind in [
+            WallpaperKind::Workshop,
+            WallpaperKind::MyProjects,
+            WallpaperKind::DefaultProjects,
+        ] {
+            let dir = match kind {
+                WallpaperKind::Workshop => self.get_workshop_path(),
+                other => self.path.join("projects").join(other.to_string()),
+            };
+            if !dir.is_dir() {
+                continue;
+            }
+
+            let mut ids: Vec<String> = fs::read_dir(dir)?
+                .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+                .collect();
+            ids.sort();
+            for id in ids {
+                let wallpaper = Wallpaper { id, kind };
+                // Folders without a readable project.json aren't wallpapers Wallpaper Engine can open
+                if let Ok(project) = self.read_project(&wallpaper) {
+                    wallpapers.push((wallpaper, project));
+                }
+            }
+        }
+        Ok(wallpapers)
+    }
+}
+
+impl WallpaperProject {
+    /// Checks every override names a property of this wallpaper and fits its type
+    pub fn validate_properties(&self, overrides: &BTreeMap<String, Value>) -> Result<()> {
+        let properties = self.general.as_ref().map(|general| &general.properties);
+
+        for (name, value) in overrides {
+            let Some(property) = properties.and_then(|properties| properties.get(name)) else {
+                Err(UnhandledError::InvalidWallpaperProperty(format!(
+                    "{} doesn't exist",
+                    name
+                )))?
+            };
+
+            if !property.accepts(value) {
+                Err(UnhandledError::InvalidWallpaperProperty(format!(
+                    "{} can't be {} as it's a {}",
+                    name, value, property.kind
+                )))?
+            }
+        }
+        Ok(())
+    }
+}
+
+impl WallpaperProjectProperty {
+    pub fn accepts(&self, value: &Value) -> bool {
+        match self.kind.as_str() {
+            "bool" => value.is_boolean(),
+            "slider" => value.as_f64().is_some_and(|value| {
+                self.min.is_none_or(|min| value >= min) && self.max.is_none_or(|max| value <= max)
+            }),
+            // Colors are stored as "r g b" with every channel between 0 and 1
+            "color" => value.as_str().is_some_and(|color| {
+                let channels: Vec<_> = color.split_whitespace().map(str::parse::<f64>).collect();
+                channels.len() == 3
+                    && channels
+                        .iter()
+                        .all(|channel| channel.as_ref().is_ok_and(|c| (0.0..=1.0).contains(c)))
+            }),
+            "combo" => self.options.iter().any(|option| option.value == *value),
+            "textinput" | "file" | "directory" => value.is_string(),
+            // Labels and groups only organize the properties panel
+            "text" | "group" => false,
+            _ => true,
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use crate::{
+    error::Result, library::settings::Settings,
+    theme::wallpaper_engine::error::InstallationNotFoundError,
+};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use vdf_parser::{VdfValue, parse_vdf_text};
+
+/// Finds steam games without relying on a single registry key
+#[derive(Debug, Clone)]
+pub struct SteamDiscovery {
+    /// Possible steam folders, in the order they're tried
+    pub candidates: Vec<PathBuf>,
+}
+
+impl SteamDiscovery {
+    pub fn new(candidates: Vec<PathBuf>) -> Self {
+        Self { candidates }
+    }
+
+    /// The settings override first, then the registry, then the usual install folders
+    pub fn system(settings: &Settings) -> Self {
+        let mut candidates: Vec<PathBuf> = settings.paths.steam.iter().cloned().collect();
+        candidates.extend(registry_steam_paths());
+        candidates.extend(well_known_steam_paths());
+        Self::new(candidates)
+    }
+
+    /// Every library of every steam folder found, each listed once
+    pub fn libraries(&self) -> Vec<PathBuf> {
+        let mut libraries: Vec<PathBuf> = vec![];
+        for steam in &self.candidates {
+            let vdf_path = steam.join("steamapps").join("libraryfolders.vdf");
+            if !vdf_path.is_file() {
+                continue;
+            }
+
+            // The steam folder is a library too, even if the file doesn't list it
+            let mut found = vec![steam.clone()];
+            found.extend(read_library_folders(&vdf_path).unwrap_or_default());
+            for library in found {
+                if !libraries.contains(&library) {
+                    libraries.push(library);
+                }
+            }
+        }
+        libraries
+    }
+
+    /// Install folder of a game, as written in its `appmanifest_<id>.acf`
+    pub fn find_app(&self, app_id: &str) -> Result<PathBuf> {
+        for library in self.libraries() {
+            let steamapps = library.join("steamapps");
+            let Some(install_dir) = read_install_dir(&steamapps, app_id) else {
+                continue;
+            };
+
+            let path = steamapps.join("common").join(install_dir);
+            if path.is_dir() {
+                return Ok(path);
+            }
+        }
+        Err(InstallationNotFoundError {}.into())
+    }
+}
+
+fn string_value(value: &VdfValue) -> Option<String> {
+    match value {
+        // Backslashes are escaped in steam's files
+        VdfValue::String(value) => Some(value.replace("\\\\", "\\")),
+        VdfValue::Block(_) => None,
+    }
+}
+
+fn read_library_folders(vdf_path: &Path) -> Result<Vec<PathBuf>> {
+    let data = parse_vdf_text(&fs::read_to_string(vdf_path)?)?;
+    let VdfValue::Block(folders) = &data.value else {
+        return Ok(vec![]);
+    };
+
+    // Keyed by "0", "1"... keep steam's order
+    let mut folders: Vec<_> = folders.iter().collect();
+    folders.sort_by_key(|(key, _)| key.parse::<u32>().unwrap_or(u32::MAX));
+
+    Ok(folders
+        .into_iter()
+        .filter_map(|(_, folder)| {
+            // Old versions of the file map the index straight to the path
+            let path = match &folder.value {
+                VdfValue::String(_) => string_value(&folder.value),
+                VdfValue::Block(_) => string_value(&folder.get_string_value("path").ok()?.value),
+            }?;
+            Some(PathBuf::from(path))
+        })
+        .collect())
+}
+
+fn read_install_dir(steamapps: &Path, app_id: &str) -> Option<String> {
+    let manifest = steamapps.join(format!("appmanifest_{}.acf", app_id));
+    let data = parse_vdf_text(&fs::read_to_string(manifest).ok()?).ok()?;
+    string_value(&data.get_string_value("installdir").ok()?.value)
+}
+
+#[cfg(windows)]
+fn registry_steam_paths() -> Vec<PathBuf> {
+    use winreg::{
+        RegKey,
+        enums::{HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE, KEY_READ, KEY_WOW64_32KEY},
+    };
+
+    let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+    let hklm = RegKey::predef(HKEY_LOCAL_MACHINE);
+    let keys = [
+        (&hkcu, "Software\\Valve\\Steam", "SteamPath", KEY_READ),
+        (
+            &hklm,
+            "SOFTWARE\\WOW6432Node\\Valve\\Steam",
+            "InstallPath",
+            KEY_READ,
+        ),
+        // 32-bit view, for systems where WOW6432Node isn't redirected
+        (
+            &hklm,
+            "SOFTWARE\\Valve\\Steam",
+            "InstallPath",
+            KEY_READ | KEY_WOW64_32KEY,
+        ),
+    ];
+
+    keys.into_iter()
+        .filter_map(|(root, path, name, flags)| {
+            let key = root.open_subkey_with_flags(path, flags).ok()?;
+            let value: String = key.get_value(name).ok()?;
+            Some(PathBuf::from(value))
+        })
+        .collect()
+}
+
+#[cfg(not(windows))]
+fn registry_steam_paths() -> Vec<PathBuf> {
+    vec![]
+}
+
+fn well_known_steam_paths() -> Vec<PathBuf> {
+    let mut paths = vec![];
+    #[cfg(windows)]
+    for var in ["ProgramFiles(x86)", "ProgramFiles"] {
+        if let Some(program_files) = std::env::var_os(var) {
+            paths.push(PathBuf::from(program_files).join("Steam"));
+        }
+    }
+    #[cfg(not(windows))]
+    if let Some(home) = dirs::home_dir() {
+        paths.push(home.join(".steam").join("steam"));
+        paths.push(home.join(".local").join("share").join("Steam"));
+        // Flatpak
+        paths.push(home.join(".var/app/com.valvesoftware.Steam/.local/share/Steam"));
+    }
+    paths
+}
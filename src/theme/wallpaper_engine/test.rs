@@ -0,0 +1,811 @@
+use crate::{
+    error::Result,
+    library::Library,
+    test::temp_dir,
+    theme::{
+        Theme, ThemeApp,
+        monitor::{MonitorFallback, MonitorResolver, MonitorSelector, parse_xrandr_monitors},
+        plan::PlannedAction,
+        wallpaper_engine::{
+            FullscreenBehavior, PlaylistMode, PlaylistOrder, ThemeWEPlaylist, ThemeWallpaperEngine,
+            Wallpaper, WallpaperEngine, WallpaperEngineBackend, WallpaperKind, WallpaperLayout,
+            config::{WallpaperEngineConfig, WallpaperEngineUserConfig},
+            control::{CommandRunner, WallpaperCommand},
+            error::MissingWallpapersError,
+            linux::{LinuxWallpaperEngine, LinuxWallpaperProcess},
+            project::{WallpaperProject, WallpaperType},
+            steam::SteamDiscovery,
+            workshop::{WorkshopItemStatus, WorkshopManifest, human_size},
+        },
+    },
+};
+use serde_json::json;
+use std::{cell::RefCell, collections::BTreeMap, fs, path::Path, rc::Rc};
+
+#[test]
+fn test_wallpaper_engine_plan() {
+    let steamapps = temp_dir("plan_wallpaper_engine");
+    let app = WallpaperEngine::new_with_path(steamapps.join("common").join("wallpaper_engine"));
+    let installed = app.get_workshop_wallpaper_path("3445801440");
+    fs::create_dir_all(installed.parent().unwrap()).unwrap();
+    fs::write(&installed, "{}").unwrap();
+
+    let theme = Theme::read_file("resources/test/cat.swapeme.json").unwrap();
+    let actions = theme
+        .wallpaper_engine
+        .unwrap()
+        .plan(&app.clone().into())
+        .unwrap();
+
+    // The second wallpaper isn't installed, missing wallpapers are listed first
+    assert!(matches!(&actions[0], PlannedAction::Other(message) if message.contains("3299228616")));
+    assert_eq!(
+        actions[1],
+        PlannedAction::Command {
+            program: app.get_app32_path(),
+            args: vec![
+                "-control".to_string(),
+                "openWallpaper".to_string(),
+                "-file".to_string(),
+                installed.to_str().unwrap().to_string(),
+                "-monitor".to_string(),
+                "0".to_string(),
+            ],
+        }
+    );
+    assert!(
+        matches!(&actions[2], PlannedAction::Command { args, .. } if args.last().unwrap() == "1")
+    );
+}
+
+#[test]
+fn test_capture_wallpaper_engine_playlist() {
+    let config: WallpaperEngineConfig = serde_json::from_str(
+        &fs::read_to_string("resources/test/wallpaper_engine/config.json").unwrap(),
+    )
+    .unwrap();
+    let theme = ThemeWallpaperEngine::from(&config.users["user"]);
+    assert_eq!(theme.layout, Some(WallpaperLayout::PerMonitor));
+
+    let wallpapers = theme.wallpapers.unwrap();
+    assert_eq!(wallpapers.len(), 1);
+    assert_eq!(wallpapers[0].monitor, MonitorSelector::Index(1));
+    assert_eq!(wallpapers[0].wallpaper.id, "3299228616");
+    assert_eq!(
+        wallpapers[0].properties,
+        Some(BTreeMap::from([
+            ("schemecolor".to_string(), json!("0.1 0.2 0.3")),
+            ("speed".to_string(), json!(2)),
+        ]))
+    );
+
+    let playlist = &theme.playlist.unwrap()[0];
+    assert_eq!(playlist.name, "Cats");
+    assert_eq!(playlist.monitor, MonitorSelector::Index(0));
+    assert_eq!(playlist.order, PlaylistOrder::Random);
+    assert_eq!(playlist.mode, PlaylistMode::Timer);
+    assert_eq!(playlist.delay, Some(30));
+    assert!(playlist.transition);
+    assert_eq!(
+        playlist
+            .items
+            .iter()
+            .map(|item| (item.id.as_str(), item.kind))
+            .collect::<Vec<_>>(),
+        vec![
+            ("3445801440", WallpaperKind::Workshop),
+            ("purrfect", WallpaperKind::MyProjects),
+        ]
+    );
+
+    // A user that never picked a wallpaper has no `wallpaperconfig`
+    let user: WallpaperEngineUserConfig = serde_json::from_str(r#"{"general": {}}"#).unwrap();
+    let theme = ThemeWallpaperEngine::from(&user);
+    assert!(theme.wallpapers.is_none());
+    assert!(theme.playlist.is_none());
+    assert!(theme.layout.is_none());
+
+    // Files outside of wallpaper engine's folders are skipped instead of guessed
+    let user: WallpaperEngineUserConfig = serde_json::from_value(json!({
+        "general": {
+            "wallpaperconfig": {
+                "layout": 0,
+                "selectedwallpapers": {
+                    "Monitor0": {
+                        "file": "C:/Wallpapers/cat/project.json",
+                        "playlist": {
+                            "name": "Mixed",
+                            "items": [
+                                "C:/Wallpapers/cat/project.json",
+                                "C:/wallpaper_engine/projects/myprojects/purrfect/project.json"
+                            ],
+                            "settings": {}
+                        }
+                    },
+                    "Monitor1": { "file": "project.json" },
+                    "Monitor2": {
+                        "file": "C:/wallpaper_engine/projects/myprojects/purrfect/project.json"
+                    }
+                }
+            }
+        },
+        "wproperties": {
+            "project.json": { "speed": 1 },
+            "C:/Wallpapers/cat/project.json": { "speed": 2 },
+            "C:/wallpaper_engine/projects/myprojects/purrfect/project.json": { "speed": 3 }
+        }
+    }))
+    .unwrap();
+    let theme = ThemeWallpaperEngine::from(&user);
+    // Stale property keys are ignored
+    let wallpapers = theme.wallpapers.unwrap();
+    assert_eq!(wallpapers.len(), 1);
+    assert_eq!(
+        wallpapers[0].properties,
+        Some(BTreeMap::from([("speed".to_string(), json!(3))]))
+    );
+    assert_eq!(
+        theme.playlist.unwrap()[0].items,
+        vec![Wallpaper {
+            id: "purrfect".to_string(),
+            kind: WallpaperKind::MyProjects
+        }]
+    );
+}
+
+#[test]
+fn test_wallpaper_engine_playlist_plan() {
+    let steamapps = temp_dir("plan_wallpaper_engine_playlist");
+    let app = WallpaperEngine::new_with_path(steamapps.join("common").join("wallpaper_engine"));
+    let theme: ThemeWallpaperEngine = serde_json::from_str(
+        r#"{ "playlist": [{
+            "name": "Cats",
+            "items": [{ "id": "3445801440", "kind": "workshop" }],
+            "order": "random",
+            "monitor": 1
+        }] }"#,
+    )
+    .unwrap();
+
+    let actions = theme.plan(&app.clone().into()).unwrap();
+    // The only item isn't installed
+    assert!(matches!(actions[0], PlannedAction::Other(_)));
+    assert_eq!(
+        actions[1],
+        PlannedAction::Other("save playlist Cats in Wallpaper Engine's config".to_string())
+    );
+    assert_eq!(
+        actions[2],
+        PlannedAction::Command {
+            program: app.get_app32_path(),
+            args: [
+                "-control",
+                "openPlaylist",
+                "-playlist",
+                "Cats",
+                "-monitor",
+                "1"
+            ]
+            .map(String::from)
+            .to_vec(),
+        }
+    );
+}
+
+#[test]
+fn test_save_wallpaper_engine_playlist() {
+    let steamapps = temp_dir("wallpaper_engine_save_playlist");
+    let app = WallpaperEngine::new_with_path(steamapps.join("common").join("wallpaper_engine"));
+    fs::create_dir_all(&app.path).unwrap();
+    fs::copy(
+        "resources/test/wallpaper_engine/config.json",
+        app.get_config_path(),
+    )
+    .unwrap();
+    let saved = || {
+        let config = app.read_config().unwrap();
+        config.users["user"].general.playlists.clone()
+    };
+
+    // Captured as is, it matches what's saved even with paths written differently
+    let cats = ThemeWEPlaylist::from_config(&saved()[0], 0);
+    assert!(cats.to_config(&app).same_as(&saved()[0]));
+
+    let mut theme: ThemeWEPlaylist = serde_json::from_value(json!({
+        "name": "Cats",
+        "items": [{ "id": "3445801440", "kind": "workshop" }],
+        "order": "sequential",
+        "monitor": 0
+    }))
+    .unwrap();
+    assert!(!theme.to_config(&app).same_as(&saved()[0]));
+    app.save_playlist_for_user("user", &theme.to_config(&app))
+        .unwrap();
+    theme.name = "Dogs".to_string();
+    app.save_playlist_for_user("user", &theme.to_config(&app))
+        .unwrap();
+
+    let playlists = saved();
+    assert_eq!(playlists.len(), 2);
+    assert_eq!(playlists[0].items.len(), 1);
+    assert_eq!(playlists[0].settings.order, PlaylistOrder::Sequential);
+    assert_eq!(playlists[0].settings.delay, Some(30));
+    assert_eq!(playlists[1].name, "Dogs");
+    assert!(playlists[1].same_as(&theme.to_config(&app)));
+
+    // Settings swapeme doesn't know about are kept
+    let config: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(app.get_config_path()).unwrap()).unwrap();
+    assert_eq!(
+        config["user"]["general"]["playlists"][0]["settings"]["updateonpause"],
+        json!(false)
+    );
+}
+
+#[test]
+fn test_set_wallpaper_engine_layout() {
+    let app = WallpaperEngine::new_with_path(temp_dir("wallpaper_engine_layout"));
+    fs::copy(
+        "resources/test/wallpaper_engine/config.json",
+        app.get_config_path(),
+    )
+    .unwrap();
+
+    app.set_layout_for_user("user", WallpaperLayout::Span)
+        .unwrap();
+    assert!(
+        app.set_layout_for_user("nobody", WallpaperLayout::Span)
+            .is_err()
+    );
+
+    let config = app.read_config().unwrap();
+    let user = &config.users["user"];
+    assert_eq!(
+        ThemeWallpaperEngine::from(user).layout,
+        Some(WallpaperLayout::Span)
+    );
+    // Everything else is left as it was
+    assert_eq!(user.general.playlists.len(), 1);
+    assert_eq!(user.wallpaper_properties.len(), 1);
+    assert!(config.path.ends_with("wallpaper_engine"));
+}
+
+#[test]
+fn test_list_wallpapers() {
+    let steamapps = temp_dir("wallpaper_engine_list");
+    let app = WallpaperEngine::new_with_path(steamapps.join("common").join("wallpaper_engine"));
+    for (path, content) in [
+        (
+            app.get_workshop_wallpaper_path("3299228616"),
+            fs::read_to_string("resources/test/wallpaper_engine/project.json").unwrap(),
+        ),
+        (
+            app.get_local_wallpaper_path("purrfect", "myprojects"),
+            r#"{ "title": "Purrfect", "type": "Video" }"#.to_string(),
+        ),
+        // Not a wallpaper, ignored
+        (
+            app.get_workshop_path().join("broken").join("readme.txt"),
+            String::new(),
+        ),
+    ] {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    let wallpapers = app.list_wallpapers().unwrap();
+    assert_eq!(wallpapers.len(), 2);
+
+    let (stars, project) = &wallpapers[0];
+    assert_eq!(stars.kind, WallpaperKind::Workshop);
+    assert_eq!(project.title.as_deref(), Some("Stars"));
+    assert_eq!(project.kind, WallpaperType::Scene);
+    assert_eq!(project.preview.as_deref(), Some("preview.gif"));
+    assert_eq!(project.tags, vec!["Nature", "Relaxing"]);
+    assert_eq!(project.content_rating.as_deref(), Some("Everyone"));
+    assert_eq!(app.describe_wallpaper(stars), "Stars (3299228616)");
+
+    let (purrfect, project) = &wallpapers[1];
+    assert_eq!(purrfect.kind, WallpaperKind::MyProjects);
+    assert_eq!(project.kind, WallpaperType::Video);
+}
+
+#[test]
+fn test_workshop_manifest() {
+    let manifest = WorkshopManifest::parse(
+        &fs::read_to_string("resources/test/wallpaper_engine/appworkshop_431960.acf").unwrap(),
+    )
+    .unwrap();
+
+    let installed = manifest.get("3445801440").unwrap();
+    assert_eq!(installed.status(), WorkshopItemStatus::Installed);
+    assert_eq!(installed.size, Some(104857600));
+    assert_eq!(
+        manifest.get("3299228616").unwrap().status(),
+        WorkshopItemStatus::PendingDownload
+    );
+    assert_eq!(
+        manifest.get("2946871012").unwrap().status(),
+        WorkshopItemStatus::PendingDownload
+    );
+    assert!(manifest.get("1234").is_none());
+
+    assert_eq!(human_size(512), "512 B");
+    assert_eq!(human_size(1536), "1.5 KB");
+    assert_eq!(human_size(104857600), "100.0 MB");
+}
+
+#[test]
+fn test_find_missing_wallpapers() {
+    let steamapps = temp_dir("wallpaper_engine_missing");
+    let app = WallpaperEngine::new_with_path(steamapps.join("common").join("wallpaper_engine"));
+    fs::create_dir_all(app.get_workshop_manifest_path().parent().unwrap()).unwrap();
+    fs::copy(
+        "resources/test/wallpaper_engine/appworkshop_431960.acf",
+        app.get_workshop_manifest_path(),
+    )
+    .unwrap();
+    let installed = app.get_workshop_wallpaper_path("3445801440");
+    fs::create_dir_all(installed.parent().unwrap()).unwrap();
+    fs::write(&installed, "{}").unwrap();
+
+    let theme: ThemeWallpaperEngine = serde_json::from_str(
+        r#"{
+            "wallpapers": [
+                { "id": "3445801440", "kind": "workshop", "monitor": 0 },
+                { "id": "3299228616", "kind": "workshop", "monitor": 1 }
+            ],
+            "playlist": [{
+                "name": "Cats",
+                "items": [
+                    { "id": "3299228616", "kind": "workshop" },
+                    { "id": "1234", "kind": "workshop" }
+                ],
+                "monitor": 2
+            }]
+        }"#,
+    )
+    .unwrap();
+
+    // Each missing wallpaper is reported once, with what steam knows about it
+    let missing = app.find_missing_wallpapers(theme.all_wallpapers());
+    assert_eq!(
+        missing
+            .iter()
+            .map(|(wallpaper, item)| (wallpaper.id.as_str(), item.as_ref().map(|i| i.status())))
+            .collect::<Vec<_>>(),
+        vec![
+            ("3299228616", Some(WorkshopItemStatus::PendingDownload)),
+            ("1234", None),
+        ]
+    );
+
+    // The batched report says how big the download is when steam knows it
+    let mut missing = missing;
+    missing[0].1.as_mut().unwrap().size = Some(52428800);
+    let report = MissingWallpapersError(missing, app).to_string();
+    assert!(report.starts_with("2 wallpapers aren't installed"));
+    assert!(report.contains(
+        "3299228616 from workshop, subscribed, waiting for steam to download it, 50.0 MB"
+    ));
+    assert!(report.ends_with("1234 from workshop, not subscribed"));
+}
+
+#[test]
+fn test_wallpaper_engine_path_override() {
+    let dir = temp_dir("wallpaper_engine_path_override");
+    let library = Library::new_with_path(dir.join("swapeme"));
+    let wallpaper_engine = dir.join("wallpaper_engine");
+    fs::create_dir_all(&wallpaper_engine).unwrap();
+
+    // Not an installation yet
+    assert!(WallpaperEngine::save_path_override(&library, wallpaper_engine.clone()).is_err());
+
+    fs::write(wallpaper_engine.join("wallpaper32.exe"), "").unwrap();
+    WallpaperEngine::save_path_override(&library, wallpaper_engine.clone()).unwrap();
+    let settings = library.read_settings().unwrap();
+    assert_eq!(
+        WallpaperEngine::new_with_settings(&settings).unwrap().path,
+        wallpaper_engine
+    );
+}
+
+#[test]
+fn test_validate_wallpaper_properties() {
+    let project: WallpaperProject = serde_json::from_str(
+        &fs::read_to_string("resources/test/wallpaper_engine/project.json").unwrap(),
+    )
+    .unwrap();
+
+    let valid = BTreeMap::from([
+        ("schemecolor".to_string(), json!("1 0.5 0")),
+        ("speed".to_string(), json!(7.5)),
+        ("stars".to_string(), json!(false)),
+        ("weather".to_string(), json!("rain")),
+    ]);
+    project.validate_properties(&valid).unwrap();
+
+    for (name, value) in [
+        ("unknown", json!(1)),
+        ("schemecolor", json!("1 0.5")),
+        ("schemecolor", json!("#ff0000")),
+        ("speed", json!(11)),
+        ("stars", json!("yes")),
+        ("weather", json!("snow")),
+    ] {
+        let invalid = BTreeMap::from([(name.to_string(), value.clone())]);
+        assert!(
+            project.validate_properties(&invalid).is_err(),
+            "{} = {}",
+            name,
+            value
+        );
+    }
+}
+
+#[test]
+fn test_wallpaper_engine_properties_plan() {
+    let steamapps = temp_dir("plan_wallpaper_engine_properties");
+    let app = WallpaperEngine::new_with_path(steamapps.join("common").join("wallpaper_engine"));
+    let installed = app.get_workshop_wallpaper_path("3299228616");
+    fs::create_dir_all(installed.parent().unwrap()).unwrap();
+    fs::copy("resources/test/wallpaper_engine/project.json", &installed).unwrap();
+
+    let theme: ThemeWallpaperEngine = serde_json::from_str(
+        r#"{ "wallpapers": [{
+            "id": "3299228616",
+            "kind": "workshop",
+            "monitor": 0,
+            "properties": { "speed": 3, "stars": false }
+        }] }"#,
+    )
+    .unwrap();
+
+    let actions = theme.plan(&app.clone().into()).unwrap();
+    assert_eq!(actions.len(), 2);
+    assert_eq!(
+        actions[1],
+        PlannedAction::Command {
+            program: app.get_app32_path(),
+            args: [
+                "-control",
+                "applyProperties",
+                "-properties",
+                r#"RAW~({"speed":3,"stars":false})~END"#,
+                "-monitor",
+                "0"
+            ]
+            .map(String::from)
+            .to_vec(),
+        }
+    );
+}
+
+#[test]
+fn test_steam_discovery() {
+    let dir = temp_dir("steam_discovery");
+    let steam = dir.join("steam");
+    let library = dir.join("games");
+    fs::create_dir_all(steam.join("steamapps")).unwrap();
+    fs::create_dir_all(library.join("steamapps/common/WE")).unwrap();
+    fs::write(
+        steam.join("steamapps/libraryfolders.vdf"),
+        format!(
+            "\"libraryfolders\"\n{{\n\t\"0\"\n\t{{\n\t\t\"path\"\t\t\"{}\"\n\t}}\n\t\"1\"\n\t{{\n\t\t\"path\"\t\t\"{}\"\n\t}}\n}}\n",
+            steam.display(),
+            library.display()
+        ),
+    )
+    .unwrap();
+    fs::write(
+        library.join("steamapps/appmanifest_431960.acf"),
+        "\"AppState\"\n{\n\t\"appid\"\t\t\"431960\"\n\t\"installdir\"\t\t\"WE\"\n}\n",
+    )
+    .unwrap();
+
+    // Folders that aren't steam installs are ignored
+    let discovery = SteamDiscovery::new(vec![dir.join("missing"), steam.clone()]);
+    assert_eq!(discovery.libraries(), vec![steam, library.clone()]);
+    assert_eq!(
+        discovery.find_app("431960").unwrap(),
+        library.join("steamapps/common/WE")
+    );
+    assert!(discovery.find_app("1").is_err());
+    assert!(SteamDiscovery::new(vec![]).find_app("431960").is_err());
+}
+
+#[test]
+fn test_linux_wallpaper_process() {
+    let args = [
+        "--screen-root",
+        "DP-1",
+        "--bg",
+        "/steam/steamapps/workshop/content/431960/3445801440",
+        "--screen-root",
+        "HDMI-1",
+        "--bg",
+        "/steam/steamapps/common/wallpaper_engine/projects/myprojects/sunset",
+        "--set-property",
+        "speed=3",
+    ]
+    .map(String::from);
+    let processes = LinuxWallpaperProcess::parse(42, &args);
+    assert_eq!(processes.len(), 2);
+    assert_eq!(processes[1].output, "HDMI-1");
+
+    let monitors = MonitorResolver::new(
+        parse_xrandr_monitors(
+            " 0: +*DP-1 2560/597x1440/336+1920+0  DP-1\n 1: +HDMI-1 1920/531x1080/299+0+0  HDMI-1",
+        ),
+        MonitorFallback::Skip,
+    );
+    let wallpaper = processes[0].to_theme_wallpaper(&monitors).unwrap();
+    assert_eq!(wallpaper.wallpaper.id, "3445801440");
+    assert_eq!(wallpaper.wallpaper.kind, WallpaperKind::Workshop);
+    // DP-1 is right of HDMI-1
+    assert_eq!(wallpaper.monitor, MonitorSelector::Position { position: 1 });
+    assert_eq!(wallpaper.properties.unwrap()["speed"], json!(3));
+
+    // Stopping DP-1 keeps HDMI-1 rendered with the same settings
+    assert_eq!(
+        processes[0].args_without_output().unwrap(),
+        [
+            "--screen-root",
+            "HDMI-1",
+            "--bg",
+            "/steam/steamapps/common/wallpaper_engine/projects/myprojects/sunset",
+            "--set-property",
+            "speed=3",
+        ]
+        .map(String::from)
+    );
+
+    let wallpaper = processes[1].wallpaper().unwrap();
+    assert_eq!(wallpaper.id, "sunset");
+    assert_eq!(wallpaper.kind, WallpaperKind::MyProjects);
+
+    // Backgrounds outside of Wallpaper Engine's folders can't be captured
+    let processes = LinuxWallpaperProcess::parse(
+        42,
+        &["--screen-root", "DP-1", "--bg", "/tmp/scene"].map(String::from),
+    );
+    assert_eq!(processes[0].wallpaper(), None);
+    assert_eq!(processes[0].args_without_output(), None);
+    let processes = LinuxWallpaperProcess::parse(
+        42,
+        &["--screen-root", "DP-1", "--bg", "/home/me/workshop/scene"].map(String::from),
+    );
+    assert_eq!(processes[0].wallpaper(), None);
+}
+
+#[test]
+fn test_linux_wallpaper_engine_plan() {
+    let steamapps = temp_dir("plan_linux_wallpaper_engine");
+    let app = WallpaperEngine::new_with_path(steamapps.join("common").join("wallpaper_engine"));
+    let installed = app.get_workshop_wallpaper_path("3445801440");
+    fs::create_dir_all(installed.parent().unwrap()).unwrap();
+    fs::write(&installed, "{}").unwrap();
+    let linux = LinuxWallpaperEngine {
+        binary: "/usr/bin/linux-wallpaperengine".into(),
+        app,
+        monitors: Some(Rc::new(parse_xrandr_monitors(
+            " 0: +*DP-1 2560/597x1440/336+1920+0  DP-1\n 1: +HDMI-1 1920/531x1080/299+0+0  HDMI-1",
+        ))),
+    };
+
+    let theme: ThemeWallpaperEngine = serde_json::from_str(
+        r#"{
+            "wallpapers": [{
+                "id": "3445801440",
+                "kind": "workshop",
+                "monitor": { "position": 0 },
+                "properties": { "speed": 3 }
+            }],
+            "playlist": [{ "name": "Cats", "items": [], "monitor": 0 }]
+        }"#,
+    )
+    .unwrap();
+
+    let actions = theme
+        .plan(&WallpaperEngineBackend::Linux(linux.clone()))
+        .unwrap();
+    assert_eq!(
+        actions[0],
+        PlannedAction::Command {
+            program: linux.binary.clone(),
+            args: vec![
+                "--screen-root".to_string(),
+                "HDMI-1".to_string(),
+                "--bg".to_string(),
+                installed.parent().unwrap().to_str().unwrap().to_string(),
+                "--set-property".to_string(),
+                "speed=3".to_string(),
+            ],
+        }
+    );
+    // Playlists can't be rendered
+    assert!(matches!(&actions[1], PlannedAction::Other(message) if message.contains("Cats")));
+
+    // Without xrandr, no output is known
+    let linux = LinuxWallpaperEngine {
+        monitors: Some(Rc::new(vec![])),
+        ..linux
+    };
+    let actions = theme.plan(&WallpaperEngineBackend::Linux(linux)).unwrap();
+    assert!(
+        matches!(&actions[0], PlannedAction::Other(message) if message.starts_with("no outputs found"))
+    );
+}
+
+/// Records the arguments of every command instead of running it
+#[derive(Default)]
+struct RecordingRunner(RefCell<Vec<Vec<String>>>);
+
+impl CommandRunner for RecordingRunner {
+    fn run(&self, _program: &Path, args: &[String]) -> Result<()> {
+        self.0.borrow_mut().push(args.to_vec());
+        Ok(())
+    }
+}
+
+#[test]
+fn test_wallpaper_command_args() {
+    for (command, args) in [
+        (WallpaperCommand::Play, vec!["-control", "play"]),
+        (WallpaperCommand::HideIcons, vec!["-control", "hideIcons"]),
+        (
+            WallpaperCommand::CloseWallpaper { monitor: None },
+            vec!["-control", "closeWallpaper"],
+        ),
+        (
+            WallpaperCommand::CloseWallpaper { monitor: Some(1) },
+            vec!["-control", "closeWallpaper", "-monitor", "1"],
+        ),
+        (
+            WallpaperCommand::OpenPlaylist {
+                name: "Cats".to_string(),
+                monitor: Some(0),
+            },
+            vec![
+                "-control",
+                "openPlaylist",
+                "-playlist",
+                "Cats",
+                "-monitor",
+                "0",
+            ],
+        ),
+        (
+            WallpaperCommand::ApplyProperties {
+                properties: BTreeMap::from([("speed".to_string(), json!(2))]),
+                monitor: None,
+            },
+            vec![
+                "-control",
+                "applyProperties",
+                "-properties",
+                r#"RAW~({"speed":2})~END"#,
+            ],
+        ),
+    ] {
+        assert_eq!(command.args(), args, "{:?}", command);
+    }
+}
+
+#[test]
+fn test_apply_wallpaper_engine_commands() {
+    let steamapps = temp_dir("apply_wallpaper_engine_commands");
+    let runner = Rc::new(RecordingRunner::default());
+    let app = WallpaperEngine::new_with_path(steamapps.join("common").join("wallpaper_engine"))
+        .with_runner(runner.clone());
+    let installed = app.get_workshop_wallpaper_path("3445801440");
+    fs::create_dir_all(installed.parent().unwrap()).unwrap();
+    fs::write(&installed, "{}").unwrap();
+
+    let theme: ThemeWallpaperEngine = serde_json::from_str(
+        r#"{
+            "wallpapers": [{ "id": "3445801440", "kind": "workshop", "monitor": 0 }],
+            "playback": { "muted": true, "hide_icons": false }
+        }"#,
+    )
+    .unwrap();
+    theme.apply(app.into()).unwrap();
+
+    assert_eq!(
+        *runner.0.borrow(),
+        vec![
+            vec![
+                "-control".to_string(),
+                "openWallpaper".to_string(),
+                "-file".to_string(),
+                installed.to_str().unwrap().to_string(),
+                "-monitor".to_string(),
+                "0".to_string(),
+            ],
+            vec!["-control".to_string(), "mute".to_string()],
+            vec!["-control".to_string(), "showIcons".to_string()],
+        ]
+    );
+}
+
+#[test]
+fn test_apply_wallpaper_engine_invalid_properties() {
+    let steamapps = temp_dir("apply_wallpaper_engine_invalid_properties");
+    let runner = Rc::new(RecordingRunner::default());
+    let app = WallpaperEngine::new_with_path(steamapps.join("common").join("wallpaper_engine"))
+        .with_runner(runner.clone());
+    let installed = app.get_workshop_wallpaper_path("3299228616");
+    fs::create_dir_all(installed.parent().unwrap()).unwrap();
+    fs::copy("resources/test/wallpaper_engine/project.json", &installed).unwrap();
+
+    let theme: ThemeWallpaperEngine = serde_json::from_str(
+        r#"{ "wallpapers": [{
+            "id": "3299228616",
+            "kind": "workshop",
+            "monitor": 0,
+            "properties": { "speed": "fast" }
+        }] }"#,
+    )
+    .unwrap();
+    // The wallpaper is still opened, only its properties are skipped
+    theme.apply(app.into()).unwrap();
+    assert_eq!(runner.0.borrow().len(), 1);
+    assert_eq!(runner.0.borrow()[0][1], "openWallpaper");
+}
+
+#[test]
+#[cfg(unix)]
+fn test_process_runner_errors() {
+    use crate::{error::Error, theme::wallpaper_engine::control::ProcessRunner};
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = temp_dir("process_runner_errors");
+    let program = dir.join("wallpaper32.exe");
+    fs::write(&program, "#!/bin/sh\necho 'not running' >&2\nexit 2\n").unwrap();
+    fs::set_permissions(&program, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let args = WallpaperCommand::Play.args();
+    match ProcessRunner.run(&program, &args) {
+        Err(Error::HandledError(error)) => assert_eq!(
+            error.to_string(),
+            "wallpaper engine -control play failed, exit status: 2: not running"
+        ),
+        _ => panic!("the command didn't fail"),
+    }
+    // Only a missing program means Wallpaper Engine isn't where swapeme looked
+    match ProcessRunner.run(&dir.join("missing.exe"), &args) {
+        Err(Error::HandledError(error)) => assert!(error.to_string().contains("installation")),
+        _ => panic!("the command didn't fail"),
+    }
+}
+
+#[test]
+fn test_set_wallpaper_engine_fullscreen() {
+    let dir = temp_dir("wallpaper_engine_fullscreen");
+    let app = WallpaperEngine::new_with_path(dir.clone());
+    fs::copy(
+        "resources/test/wallpaper_engine/config.json",
+        app.get_config_path(),
+    )
+    .unwrap();
+
+    app.set_fullscreen_for_user("user", FullscreenBehavior::Pause)
+        .unwrap();
+    let config = app.read_config().unwrap();
+    let general = &config.users["user"].general;
+    assert_eq!(general.fullscreen, Some(FullscreenBehavior::Pause));
+    // The rest of the config is left as is, in the same order
+    assert_eq!(general.playlists.len(), 1);
+    let content = fs::read_to_string(app.get_config_path()).unwrap();
+    assert!(content.find("wproperties").unwrap() < content.find("general").unwrap());
+    assert!(
+        app.set_fullscreen_for_user("nobody", FullscreenBehavior::Run)
+            .is_err()
+    );
+
+    let theme = ThemeWallpaperEngine::from(&config.users["user"]);
+    assert_eq!(
+        theme.playback.unwrap().fullscreen,
+        Some(FullscreenBehavior::Pause)
+    );
+}
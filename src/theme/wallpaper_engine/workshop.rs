@@ -0,0 +1,160 @@
+use crate::{
+    error::Result,
+    theme::wallpaper_engine::{Wallpaper, WallpaperEngine, WallpaperKind},
+};
+use std::{collections::HashMap, fmt::Display, fs, path::PathBuf};
+use vdf_parser::{VdfAttribute, VdfValue, parse_vdf_text};
+
+/// What steam knows about a workshop item, from `appworkshop_431960.acf`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WorkshopItem {
+    pub subscribed: bool,
+    pub installed: bool,
+    /// Installed, but steam has a newer version to download
+    pub needs_update: bool,
+    /// Bytes on disk once installed
+    pub size: Option<u64>,
+}
+
+/// Bytes in the largest unit that keeps at least one of it, like `1.5 MB`
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorkshopItemStatus {
+    Installed,
+    /// Subscribed but steam didn't download it yet
+    PendingDownload,
+    NotSubscribed,
+}
+
+impl WorkshopItem {
+    pub fn status(&self) -> WorkshopItemStatus {
+        if self.installed && !self.needs_update {
+            WorkshopItemStatus::Installed
+        } else if self.subscribed {
+            WorkshopItemStatus::PendingDownload
+        } else if self.installed {
+            WorkshopItemStatus::Installed
+        } else {
+            WorkshopItemStatus::NotSubscribed
+        }
+    }
+}
+
+impl Display for WorkshopItemStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            WorkshopItemStatus::Installed => "installed",
+            WorkshopItemStatus::PendingDownload => "subscribed, waiting for steam to download it",
+            WorkshopItemStatus::NotSubscribed => "not subscribed",
+        })
+    }
+}
+
+/// Steam's workshop manifest for Wallpaper Engine
+#[derive(Debug, Default)]
+pub struct WorkshopManifest {
+    pub items: HashMap<String, WorkshopItem>,
+}
+
+fn block(attribute: &VdfAttribute, key: &str) -> Option<HashMap<String, HashMap<String, String>>> {
+    let VdfValue::Block(items) = &attribute.get_string_value(key).ok()?.value else {
+        return None;
+    };
+
+    Some(
+        items
+            .iter()
+            .map(|(id, item)| {
+                let fields = match &item.value {
+                    VdfValue::Block(fields) => fields
+                        .iter()
+                        .filter_map(|(name, field)| match &field.value {
+                            VdfValue::String(value) => Some((name.to_lowercase(), value.clone())),
+                            VdfValue::Block(_) => None,
+                        })
+                        .collect(),
+                    VdfValue::String(_) => HashMap::new(),
+                };
+                (id.clone(), fields)
+            })
+            .collect(),
+    )
+}
+
+impl WorkshopManifest {
+    pub fn parse(content: &str) -> Result<Self> {
+        let data = parse_vdf_text(content)?;
+        let installed = block(&data, "WorkshopItemsInstalled").unwrap_or_default();
+        let details = block(&data, "WorkshopItemDetails").unwrap_or_default();
+
+        let mut items: HashMap<String, WorkshopItem> = HashMap::new();
+        for (id, fields) in &installed {
+            let item = items.entry(id.clone()).or_default();
+            item.installed = true;
+            item.size = fields.get("size").and_then(|size| size.parse().ok());
+        }
+        for (id, fields) in &details {
+            let item = items.entry(id.clone()).or_default();
+            item.subscribed = fields.get("subscribedby").is_some_and(|user| user != "0");
+            item.needs_update = match (fields.get("manifest"), fields.get("latest_manifest")) {
+                (Some(manifest), Some(latest)) => manifest != latest,
+                _ => false,
+            };
+        }
+        Ok(Self { items })
+    }
+
+    pub fn get(&self, id: &str) -> Option<&WorkshopItem> {
+        self.items.get(id)
+    }
+}
+
+impl WallpaperEngine {
+    pub fn get_workshop_manifest_path(&self) -> PathBuf {
+        self.get_workshop_path()
+            .parent()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .join(format!("appworkshop_{}.acf", Self::STEAM_GAME_ID))
+    }
+
+    pub fn read_workshop_manifest(&self) -> Result<WorkshopManifest> {
+        WorkshopManifest::parse(&fs::read_to_string(self.get_workshop_manifest_path())?)
+    }
+
+    /// Wallpapers missing from disk, with what steam knows about the workshop ones
+    pub fn find_missing_wallpapers<'a>(
+        &self,
+        wallpapers: impl IntoIterator<Item = &'a Wallpaper>,
+    ) -> Vec<(Wallpaper, Option<WorkshopItem>)> {
+        let manifest = self.read_workshop_manifest().unwrap_or_default();
+        let mut missing: Vec<(Wallpaper, Option<WorkshopItem>)> = vec![];
+        for wallpaper in wallpapers {
+            if self.get_wallpaper_path(wallpaper).exists()
+                || missing.iter().any(|(other, _)| other == wallpaper)
+            {
+                continue;
+            }
+            let item = match wallpaper.kind {
+                WallpaperKind::Workshop => manifest.get(&wallpaper.id).cloned(),
+                _ => None,
+            };
+            missing.push((wallpaper.clone(), item));
+        }
+        missing
+    }
+}
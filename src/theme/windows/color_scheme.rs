@@ -1,88 +1,192 @@
 use crate::{
     color::{ColorTheme, RgbaHexColor},
     error::Result,
-    theme::ThemeApp,
+    theme::{
+        ThemeApp,
+        plan::PlannedAction,
+        windows::system::{RegistryValue, Windows, get_windows},
+    },
 };
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use windows::Win32::{
-    Foundation::{LPARAM, WPARAM},
-    UI::WindowsAndMessaging::{
-        HWND_BROADCAST, SMTO_ABORTIFHUNG, SendMessageTimeoutW, WM_SETTINGCHANGE,
-    },
-};
-use winreg::{RegKey, enums::HKEY_CURRENT_USER};
 
-#[derive(Deserialize, Serialize, JsonSchema)]
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
 pub struct WindowsColorScheme {
-    system_color_theme: Option<ColorTheme>,
-    app_theme: Option<ColorTheme>,
-    accent_color: Option<RgbaHexColor>,
+    pub system_color_theme: Option<ColorTheme>,
+    pub app_theme: Option<ColorTheme>,
+    pub accent_color: Option<RgbaHexColor>,
+    /// Show the accent color on title bars and window borders
+    pub accent_on_title_bars: Option<bool>,
+    /// Show the accent color on Start and the taskbar
+    pub accent_on_start_and_taskbar: Option<bool>,
 }
 
-impl ThemeApp for WindowsColorScheme {
-    const NAME: &'static str = "Windows color scheme";
-    type App = ();
+/// Shades Windows derives from the accent color, lightest first
+pub fn accent_palette(accent: RgbaHexColor) -> [RgbaHexColor; 8] {
+    let white = RgbaHexColor::new(0xff, 0xff, 0xff, accent.0.a);
+    let black = RgbaHexColor::new(0x00, 0x00, 0x00, accent.0.a);
+    [
+        accent.mix(white, 0.6),
+        accent.mix(white, 0.4),
+        accent.mix(white, 0.2),
+        accent,
+        accent.mix(black, 0.25),
+        accent.mix(black, 0.45),
+        accent.mix(black, 0.7),
+        accent,
+    ]
+}
 
-    fn apply(&self, app: Self::App) -> Result<()> {
-        let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+/// `AccentPalette` registry value, each shade as `RR GG BB 00`
+pub fn accent_palette_bytes(accent: RgbaHexColor) -> Vec<u8> {
+    accent_palette(accent)
+        .iter()
+        .flat_map(|color| [color.0.r, color.0.g, color.0.b, 0])
+        .collect()
+}
 
-        let (key, _) = hkcu
-            .create_subkey("Software\\Microsoft\\Windows\\CurrentVersion\\Themes\\Personalize")?;
+impl WindowsColorScheme {
+    pub const PERSONALIZE_KEY: &str =
+        "Software\\Microsoft\\Windows\\CurrentVersion\\Themes\\Personalize";
+    pub const DWM_KEY: &str = "Software\\Microsoft\\Windows\\DWM";
+    pub const ACCENT_KEY: &str = "Software\\Microsoft\\Windows\\CurrentVersion\\Explorer\\Accent";
 
-        for (reg_key, option_theme) in [
+    /// Every registry value applying this scheme writes, as `(key, name, value)`
+    pub fn registry_values(&self) -> Vec<(&'static str, &'static str, RegistryValue)> {
+        let mut values = vec![];
+
+        for (name, theme) in [
             ("AppsUseLightTheme", &self.app_theme),
             ("SystemUsesLightTheme", &self.system_color_theme),
         ] {
-            if let Some(theme) = option_theme {
-                match theme {
+            if let Some(theme) = theme {
+                let value = match theme {
                     ColorTheme::Light => 1u32,
                     ColorTheme::Dark => 0u32,
                 };
-                key.set_value(
-                    reg_key,
-                    &match theme {
-                        ColorTheme::Light => 1u32,
-                        ColorTheme::Dark => 0u32,
-                    },
-                )?;
+                values.push((Self::PERSONALIZE_KEY, name, RegistryValue::Dword(value)));
             }
         }
 
-        // let (dwm, _) = hkcu.create_subkey("Software\\Microsoft\\Windows\\DWM")?;
-
-        unsafe {
-            SendMessageTimeoutW(
-                HWND_BROADCAST,
-                WM_SETTINGCHANGE,
-                WPARAM(0),
-                LPARAM(
-                    "ImmersiveColorSet"
-                        .encode_utf16()
-                        .chain(Some(0))
-                        .collect::<Vec<u16>>()
-                        .as_ptr()
-                        .addr()
-                        .try_into()
-                        .unwrap(),
+        if let Some(accent) = self.accent_color {
+            let palette = accent_palette(accent);
+            values.extend([
+                (
+                    Self::DWM_KEY,
+                    "AccentColor",
+                    RegistryValue::Dword(accent.to_abgr()),
+                ),
+                (
+                    Self::DWM_KEY,
+                    "ColorizationColor",
+                    RegistryValue::Dword(accent.to_argb()),
                 ),
-                SMTO_ABORTIFHUNG,
-                5000,
-                Some(std::ptr::null_mut()),
-            );
+                (
+                    Self::DWM_KEY,
+                    "ColorizationAfterglow",
+                    RegistryValue::Dword(accent.to_argb()),
+                ),
+                (
+                    Self::ACCENT_KEY,
+                    "AccentPalette",
+                    RegistryValue::Binary(accent_palette_bytes(accent)),
+                ),
+                (
+                    Self::ACCENT_KEY,
+                    "AccentColorMenu",
+                    RegistryValue::Dword(palette[4].to_abgr()),
+                ),
+                (
+                    Self::ACCENT_KEY,
+                    "StartColorMenu",
+                    RegistryValue::Dword(palette[5].to_abgr()),
+                ),
+            ]);
+        }
+
+        for (key, enabled) in [
+            (Self::DWM_KEY, self.accent_on_title_bars),
+            (Self::PERSONALIZE_KEY, self.accent_on_start_and_taskbar),
+        ] {
+            if let Some(enabled) = enabled {
+                values.push((key, "ColorPrevalence", RegistryValue::Dword(enabled.into())));
+            }
+        }
+
+        values
+    }
+
+    fn read_color_theme(app: &Windows, name: &str) -> Result<Option<ColorTheme>> {
+        Ok(match app.get_registry_value(Self::PERSONALIZE_KEY, name)? {
+            Some(RegistryValue::Dword(0)) => Some(ColorTheme::Dark),
+            Some(RegistryValue::Dword(_)) => Some(ColorTheme::Light),
+            _ => None,
+        })
+    }
+
+    fn read_flag(app: &Windows, key: &str, name: &str) -> Result<Option<bool>> {
+        Ok(match app.get_registry_value(key, name)? {
+            Some(RegistryValue::Dword(value)) => Some(value != 0),
+            _ => None,
+        })
+    }
+
+    /// `AccentColor` is stored as ABGR, the older `ColorizationColor` as ARGB
+    fn read_accent_color(app: &Windows) -> Result<Option<RgbaHexColor>> {
+        if let Some(RegistryValue::Dword(value)) =
+            app.get_registry_value(Self::DWM_KEY, "AccentColor")?
+        {
+            return Ok(Some(RgbaHexColor::from_abgr(value)));
+        }
+
+        Ok(
+            match app.get_registry_value(Self::DWM_KEY, "ColorizationColor")? {
+                Some(RegistryValue::Dword(value)) => Some(RgbaHexColor::from_argb(value)),
+                _ => None,
+            },
+        )
+    }
+}
+
+impl ThemeApp for WindowsColorScheme {
+    const NAME: &'static str = "Windows color scheme";
+    type App = Windows;
+
+    fn apply(&self, app: Self::App) -> Result<()> {
+        for (key, name, value) in self.registry_values() {
+            app.set_registry_value(key, name, value)?;
         }
-        Ok(())
+
+        app.broadcast_setting_change("ImmersiveColorSet")
+    }
+
+    fn plan(&self, _app: &Self::App) -> Result<Vec<PlannedAction>> {
+        Ok(self
+            .registry_values()
+            .into_iter()
+            .map(|(path, name, value)| PlannedAction::SetRegistryValue {
+                path: path.to_string(),
+                name: name.to_string(),
+                value,
+            })
+            .collect())
     }
 
     fn get_current(app: Self::App) -> Result<Self> {
         Ok(Self {
-            system_color_theme: None,
-            app_theme: None,
-            accent_color: None,
+            system_color_theme: Self::read_color_theme(&app, "SystemUsesLightTheme")?,
+            app_theme: Self::read_color_theme(&app, "AppsUseLightTheme")?,
+            accent_color: Self::read_accent_color(&app)?,
+            accent_on_title_bars: Self::read_flag(&app, Self::DWM_KEY, "ColorPrevalence")?,
+            accent_on_start_and_taskbar: Self::read_flag(
+                &app,
+                Self::PERSONALIZE_KEY,
+                "ColorPrevalence",
+            )?,
         })
     }
 
-    fn get_app() -> Option<Self::App> {
-        Some(())
+    fn get_app() -> Result<Option<Self::App>> {
+        Ok(get_windows())
     }
 }
@@ -1,40 +1,68 @@
 use crate::{
+    cli::display_success,
     error::Result,
     theme::{
         ThemeApp,
-        windows::{color_scheme::WindowsColorScheme, wallpaper::WindowsWallpaper},
+        plan::PlannedAction,
+        windows::{
+            color_scheme::WindowsColorScheme,
+            system::{Windows, get_windows},
+            wallpaper::WindowsWallpaper,
+        },
     },
 };
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 pub mod color_scheme;
+pub mod system;
+#[cfg(test)]
+pub mod test;
 pub mod wallpaper;
 
 #[derive(Deserialize, Serialize, JsonSchema)]
 pub struct ThemeWindows {
-    wallpaper: Option<WindowsWallpaper>,
-    color_scheme: Option<WindowsColorScheme>,
+    pub wallpaper: Option<WindowsWallpaper>,
+    pub color_scheme: Option<WindowsColorScheme>,
 }
 
 impl ThemeApp for ThemeWindows {
     const NAME: &'static str = "Windows";
-    type App = ();
+    type App = Windows;
 
-    fn get_app() -> Option<Self::App> {
-        Some(())
+    fn get_app() -> Result<Option<Self::App>> {
+        Ok(get_windows())
     }
 
-    fn apply(&self, _app: Self::App) -> crate::error::Result<()> {
-        todo!();
+    fn apply(&self, app: Self::App) -> Result<()> {
+        if let Some(color_scheme) = &self.color_scheme {
+            color_scheme.apply(app.clone())?;
+            display_success("Applied windows color scheme");
+        }
+
+        if let Some(wallpaper) = &self.wallpaper {
+            wallpaper.apply(app)?;
+            display_success("Applied windows wallpaper");
+        }
+
         Ok(())
     }
 
-    fn get_current(_app: Self::App) -> Result<Self> {
-        todo!();
+    fn plan(&self, app: &Self::App) -> Result<Vec<PlannedAction>> {
+        let mut actions = vec![];
+        if let Some(color_scheme) = &self.color_scheme {
+            actions.extend(color_scheme.plan(app)?);
+        }
+        if let Some(wallpaper) = &self.wallpaper {
+            actions.extend(wallpaper.plan(app)?);
+        }
+        Ok(actions)
+    }
+
+    fn get_current(app: Self::App) -> Result<Self> {
         Ok(Self {
-            wallpaper: None,
-            color_scheme: None,
+            wallpaper: WindowsWallpaper::capture(&app)?,
+            color_scheme: Some(WindowsColorScheme::get_current(app)?),
         })
     }
 }
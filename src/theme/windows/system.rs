@@ -0,0 +1,241 @@
+use crate::error::Result;
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::Duration,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegistryValue {
+    Dword(u32),
+    Binary(Vec<u8>),
+    String(String),
+}
+
+impl Display for RegistryValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RegistryValue::Dword(value) => write!(f, "{:#010x}", value),
+            RegistryValue::Binary(bytes) => {
+                for byte in bytes {
+                    write!(f, "{:02x}", byte)?;
+                }
+                Ok(())
+            }
+            RegistryValue::String(value) => write!(f, "{:?}", value),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Slideshow {
+    pub folder: PathBuf,
+    pub interval: Duration,
+    pub shuffle: bool,
+}
+
+/// Registry and system parameters access, faked in tests
+pub trait WindowsSystem {
+    /// Reads a value under `HKEY_CURRENT_USER`, `None` if the key or value doesn't exist
+    fn get_registry_value(&self, path: &str, name: &str) -> Result<Option<RegistryValue>>;
+    /// Writes a value under `HKEY_CURRENT_USER`, creating the key if needed
+    fn set_registry_value(&self, path: &str, name: &str, value: RegistryValue) -> Result<()>;
+    fn get_wallpaper(&self) -> Result<Option<PathBuf>>;
+    fn set_wallpaper(&self, path: &Path) -> Result<()>;
+    /// The running slideshow, `None` when the desktop shows a single picture
+    fn get_slideshow(&self) -> Result<Option<Slideshow>>;
+    fn set_slideshow(&self, slideshow: &Slideshow) -> Result<()>;
+    /// Notifies running apps that a setting changed, e.g. `ImmersiveColorSet`
+    fn broadcast_setting_change(&self, area: &str) -> Result<()>;
+}
+
+pub type Windows = Rc<dyn WindowsSystem>;
+
+/// The running system, only available on Windows
+pub fn get_windows() -> Option<Windows> {
+    #[cfg(windows)]
+    return Some(Rc::new(native::NativeWindowsSystem));
+    #[cfg(not(windows))]
+    None
+}
+
+#[cfg(windows)]
+mod native {
+    use super::{RegistryValue, Slideshow, WindowsSystem};
+    use crate::error::Result;
+    use std::{
+        path::{Path, PathBuf},
+        time::Duration,
+    };
+    use windows::{
+        Win32::{
+            Foundation::{LPARAM, WPARAM},
+            System::Com::{
+                CLSCTX_ALL, COINIT_APARTMENTTHREADED, CoCreateInstance, CoInitializeEx,
+                CoTaskMemFree,
+            },
+            UI::{
+                Shell::{
+                    DESKTOP_SLIDESHOW_OPTIONS, DSO_SHUFFLEIMAGES, DSS_SLIDESHOW, DesktopWallpaper,
+                    IDesktopWallpaper, IShellItem, IShellItemArray, SHCreateItemFromParsingName,
+                    SHCreateShellItemArrayFromShellItem, SIGDN_FILESYSPATH,
+                },
+                WindowsAndMessaging::{
+                    HWND_BROADCAST, SMTO_ABORTIFHUNG, SPI_GETDESKWALLPAPER, SPI_SETDESKWALLPAPER,
+                    SPIF_SENDCHANGE, SPIF_UPDATEINIFILE, SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS,
+                    SendMessageTimeoutW, SystemParametersInfoW, WM_SETTINGCHANGE,
+                },
+            },
+        },
+        core::HSTRING,
+    };
+    use winreg::{
+        RegKey, RegValue,
+        enums::{HKEY_CURRENT_USER, REG_BINARY, REG_DWORD, REG_EXPAND_SZ, REG_SZ},
+    };
+
+    pub struct NativeWindowsSystem;
+
+    fn to_wide(value: &str) -> Vec<u16> {
+        value.encode_utf16().chain(Some(0)).collect()
+    }
+
+    fn desktop_wallpaper() -> Result<IDesktopWallpaper> {
+        unsafe {
+            // Only fails when COM was already initialized differently on this thread
+            let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
+            Ok(CoCreateInstance(&DesktopWallpaper, None, CLSCTX_ALL)?)
+        }
+    }
+
+    impl WindowsSystem for NativeWindowsSystem {
+        fn get_registry_value(&self, path: &str, name: &str) -> Result<Option<RegistryValue>> {
+            let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+            let Ok(key) = hkcu.open_subkey(path) else {
+                return Ok(None);
+            };
+            let Ok(raw) = key.get_raw_value(name) else {
+                return Ok(None);
+            };
+
+            Ok(Some(match raw.vtype {
+                REG_DWORD => RegistryValue::Dword(key.get_value(name)?),
+                REG_SZ | REG_EXPAND_SZ => RegistryValue::String(key.get_value(name)?),
+                _ => RegistryValue::Binary(raw.bytes),
+            }))
+        }
+
+        fn set_registry_value(&self, path: &str, name: &str, value: RegistryValue) -> Result<()> {
+            let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+            let (key, _) = hkcu.create_subkey(path)?;
+
+            match value {
+                RegistryValue::Dword(value) => key.set_value(name, &value)?,
+                RegistryValue::String(value) => key.set_value(name, &value)?,
+                RegistryValue::Binary(bytes) => key.set_raw_value(
+                    name,
+                    &RegValue {
+                        bytes,
+                        vtype: REG_BINARY,
+                    },
+                )?,
+            }
+            Ok(())
+        }
+
+        fn get_wallpaper(&self) -> Result<Option<PathBuf>> {
+            let mut buffer = [0u16; 260];
+            unsafe {
+                SystemParametersInfoW(
+                    SPI_GETDESKWALLPAPER,
+                    buffer.len() as u32,
+                    Some(buffer.as_mut_ptr().cast()),
+                    SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS(0),
+                )
+            }?;
+
+            let len = buffer.iter().position(|c| *c == 0).unwrap_or(buffer.len());
+            let path = String::from_utf16_lossy(&buffer[..len]);
+            Ok((!path.is_empty()).then(|| PathBuf::from(path)))
+        }
+
+        fn set_wallpaper(&self, path: &Path) -> Result<()> {
+            let mut path = to_wide(&path.to_string_lossy());
+            unsafe {
+                SystemParametersInfoW(
+                    SPI_SETDESKWALLPAPER,
+                    0,
+                    Some(path.as_mut_ptr().cast()),
+                    SPIF_UPDATEINIFILE | SPIF_SENDCHANGE,
+                )
+            }?;
+            Ok(())
+        }
+
+        fn get_slideshow(&self) -> Result<Option<Slideshow>> {
+            let desktop = desktop_wallpaper()?;
+            unsafe {
+                if desktop.GetStatus()?.0 & DSS_SLIDESHOW.0 == 0 {
+                    return Ok(None);
+                }
+
+                let name = desktop
+                    .GetSlideshow()?
+                    .GetItemAt(0)?
+                    .GetDisplayName(SIGDN_FILESYSPATH)?;
+                let folder = PathBuf::from(String::from_utf16_lossy(name.as_wide()));
+                CoTaskMemFree(Some(name.0 as _));
+
+                let mut options = DESKTOP_SLIDESHOW_OPTIONS(0);
+                let mut interval = 0;
+                desktop.GetSlideshowOptions(&mut options, &mut interval)?;
+
+                Ok(Some(Slideshow {
+                    folder,
+                    interval: Duration::from_millis(interval.into()),
+                    shuffle: options.0 & DSO_SHUFFLEIMAGES.0 != 0,
+                }))
+            }
+        }
+
+        fn set_slideshow(&self, slideshow: &Slideshow) -> Result<()> {
+            let desktop = desktop_wallpaper()?;
+            let folder = HSTRING::from(slideshow.folder.as_path());
+            unsafe {
+                let item: IShellItem = SHCreateItemFromParsingName(&folder, None)?;
+                let items: IShellItemArray = SHCreateShellItemArrayFromShellItem(&item)?;
+                desktop.SetSlideshow(&items)?;
+                desktop.SetSlideshowOptions(
+                    if slideshow.shuffle {
+                        DSO_SHUFFLEIMAGES
+                    } else {
+                        DESKTOP_SLIDESHOW_OPTIONS(0)
+                    },
+                    slideshow
+                        .interval
+                        .as_millis()
+                        .try_into()
+                        .unwrap_or(u32::MAX),
+                )?;
+            }
+            Ok(())
+        }
+
+        fn broadcast_setting_change(&self, area: &str) -> Result<()> {
+            let area = to_wide(area);
+            unsafe {
+                SendMessageTimeoutW(
+                    HWND_BROADCAST,
+                    WM_SETTINGCHANGE,
+                    WPARAM(0),
+                    LPARAM(area.as_ptr().addr().try_into().unwrap()),
+                    SMTO_ABORTIFHUNG,
+                    5000,
+                    Some(std::ptr::null_mut()),
+                );
+            }
+            Ok(())
+        }
+    }
+}
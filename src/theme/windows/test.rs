@@ -0,0 +1,431 @@
+use crate::{
+    color::{ColorTheme, RgbaHexColor},
+    error::{Error, Result, UnhandledError},
+    library::cache::Cache,
+    test::temp_dir,
+    theme::{
+        ThemeApp,
+        plan::PlannedAction,
+        windows::{
+            ThemeWindows,
+            color_scheme::{WindowsColorScheme, accent_palette, accent_palette_bytes},
+            system::{RegistryValue, Slideshow, Windows, WindowsSystem},
+            wallpaper::{WallpaperFit, WindowsWallpaper},
+        },
+    },
+};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::Duration,
+};
+
+/// In-memory registry and desktop
+#[derive(Default)]
+pub struct FakeWindowsSystem {
+    pub registry: RefCell<HashMap<(String, String), RegistryValue>>,
+    pub wallpaper: RefCell<Option<PathBuf>>,
+    pub slideshow: RefCell<Option<Slideshow>>,
+    pub broadcasts: RefCell<Vec<String>>,
+}
+
+impl FakeWindowsSystem {
+    pub fn new_app() -> (Rc<Self>, Windows) {
+        let system = Rc::new(Self::default());
+        (system.clone(), system)
+    }
+
+    pub fn get(&self, path: &str, name: &str) -> Option<RegistryValue> {
+        self.registry
+            .borrow()
+            .get(&(path.to_string(), name.to_string()))
+            .cloned()
+    }
+}
+
+impl WindowsSystem for FakeWindowsSystem {
+    fn get_registry_value(&self, path: &str, name: &str) -> Result<Option<RegistryValue>> {
+        Ok(self.get(path, name))
+    }
+
+    fn set_registry_value(&self, path: &str, name: &str, value: RegistryValue) -> Result<()> {
+        self.registry
+            .borrow_mut()
+            .insert((path.to_string(), name.to_string()), value);
+        Ok(())
+    }
+
+    fn get_wallpaper(&self) -> Result<Option<PathBuf>> {
+        Ok(self.wallpaper.borrow().clone())
+    }
+
+    fn set_wallpaper(&self, path: &Path) -> Result<()> {
+        *self.wallpaper.borrow_mut() = Some(path.to_path_buf());
+        *self.slideshow.borrow_mut() = None;
+        Ok(())
+    }
+
+    fn get_slideshow(&self) -> Result<Option<Slideshow>> {
+        Ok(self.slideshow.borrow().clone())
+    }
+
+    fn set_slideshow(&self, slideshow: &Slideshow) -> Result<()> {
+        *self.slideshow.borrow_mut() = Some(slideshow.clone());
+        Ok(())
+    }
+
+    fn broadcast_setting_change(&self, area: &str) -> Result<()> {
+        self.broadcasts.borrow_mut().push(area.to_string());
+        Ok(())
+    }
+}
+
+#[test]
+fn test_apply_windows() {
+    let (system, app) = FakeWindowsSystem::new_app();
+    let theme: ThemeWindows = serde_json::from_str(
+        r#"{ "color_scheme": { "app_theme": "dark", "system_color_theme": "light" } }"#,
+    )
+    .unwrap();
+
+    theme.apply(app).unwrap();
+
+    assert_eq!(
+        system.get(WindowsColorScheme::PERSONALIZE_KEY, "AppsUseLightTheme"),
+        Some(RegistryValue::Dword(0))
+    );
+    assert_eq!(
+        system.get(WindowsColorScheme::PERSONALIZE_KEY, "SystemUsesLightTheme"),
+        Some(RegistryValue::Dword(1))
+    );
+    assert_eq!(*system.broadcasts.borrow(), vec!["ImmersiveColorSet"]);
+}
+
+#[test]
+fn test_apply_single_wallpaper() {
+    let dir = temp_dir("windows_single_wallpaper");
+    let image = dir.join("stars.png");
+    fs::write(&image, "stars").unwrap();
+    let cache = Cache::new_with_path(dir.join("cache"));
+
+    let (system, app) = FakeWindowsSystem::new_app();
+    let wallpaper = WindowsWallpaper::Single {
+        url: image.to_string_lossy().to_string(),
+        fit: Some(WallpaperFit::Tile),
+    };
+    wallpaper.apply_with_cache(&app, &cache).unwrap();
+
+    // The wallpaper points to a copy in the cache, not to the original file
+    let set = system.wallpaper.borrow().clone().unwrap();
+    assert!(set.starts_with(&cache.path));
+    assert_eq!(fs::read_to_string(set).unwrap(), "stars");
+    assert_eq!(
+        system.get(WindowsWallpaper::DESKTOP_KEY, "WallpaperStyle"),
+        Some(RegistryValue::String("0".to_string()))
+    );
+    assert_eq!(
+        system.get(WindowsWallpaper::DESKTOP_KEY, "TileWallpaper"),
+        Some(RegistryValue::String("1".to_string()))
+    );
+}
+
+#[test]
+fn test_apply_slideshow() {
+    let dir = temp_dir("windows_slideshow");
+    let mut urls = vec![];
+    // Same file name in two folders, both must end up in the slideshow
+    for folder in ["a", "b"] {
+        fs::create_dir_all(dir.join(folder)).unwrap();
+        let image = dir.join(folder).join("image.png");
+        fs::write(&image, folder).unwrap();
+        urls.push(image.to_string_lossy().to_string());
+    }
+    let cache = Cache::new_with_path(dir.join("cache"));
+
+    let (system, app) = FakeWindowsSystem::new_app();
+    let wallpaper = WindowsWallpaper::Diaporama {
+        urls,
+        folder: None,
+        interval: Some(60),
+        shuffle: Some(true),
+        fit: Some(WallpaperFit::Fill),
+    };
+    wallpaper.apply_with_cache(&app, &cache).unwrap();
+
+    let slideshow = system.slideshow.borrow().clone().unwrap();
+    assert_eq!(slideshow.interval, Duration::from_secs(60));
+    assert!(slideshow.shuffle);
+    assert_eq!(fs::read_dir(&slideshow.folder).unwrap().count(), 2);
+    assert_eq!(
+        system.get(WindowsWallpaper::DESKTOP_KEY, "WallpaperStyle"),
+        Some(RegistryValue::String("10".to_string()))
+    );
+
+    // Without pictures there's nothing to show, instead of an empty slideshow
+    let mut empty = WindowsWallpaper::Diaporama {
+        urls: vec![],
+        folder: None,
+        interval: None,
+        shuffle: None,
+        fit: None,
+    };
+    let is_empty_error = |result: Result<_>| {
+        matches!(
+            result,
+            Err(Error::UnhandledError(UnhandledError::EmptyDiaporama))
+        )
+    };
+    assert!(is_empty_error(empty.resolve_assets(&dir)));
+    assert!(is_empty_error(empty.plan(&app).map(|_| ())));
+    assert!(is_empty_error(empty.apply_with_cache(&app, &cache)));
+}
+
+#[test]
+fn test_capture_windows_wallpaper() {
+    let (system, app) = FakeWindowsSystem::new_app();
+    let current = ThemeWindows::get_current(app.clone()).unwrap();
+    assert!(current.wallpaper.is_none());
+    assert!(current.color_scheme.is_some());
+
+    system
+        .set_wallpaper(Path::new("C:\\wallpapers\\cat.png"))
+        .unwrap();
+    let current = ThemeWindows::get_current(app).unwrap();
+    assert!(matches!(
+        current.wallpaper,
+        Some(WindowsWallpaper::Single { url, fit: None }) if url == "C:\\wallpapers\\cat.png"
+    ));
+}
+
+#[test]
+fn test_wallpaper_round_trip() {
+    let (system, app) = FakeWindowsSystem::new_app();
+    let slideshow = Slideshow {
+        folder: PathBuf::from("C:\\wallpapers"),
+        interval: Duration::from_secs(600),
+        shuffle: false,
+    };
+    system.set_slideshow(&slideshow).unwrap();
+    for (name, value) in [("WallpaperStyle", "22"), ("TileWallpaper", "0")] {
+        system
+            .set_registry_value(
+                WindowsWallpaper::DESKTOP_KEY,
+                name,
+                RegistryValue::String(value.to_string()),
+            )
+            .unwrap();
+    }
+
+    let captured = WindowsWallpaper::get_current(app).unwrap();
+    assert_eq!(
+        captured,
+        WindowsWallpaper::Diaporama {
+            urls: vec![],
+            folder: Some("C:\\wallpapers".to_string()),
+            interval: Some(600),
+            shuffle: Some(false),
+            fit: Some(WallpaperFit::Span),
+        }
+    );
+
+    // The captured folder is used as is, without going through the cache
+    let (target, target_app) = FakeWindowsSystem::new_app();
+    let cache = Cache::new_with_path(temp_dir("windows_wallpaper_round_trip"));
+    captured.apply_with_cache(&target_app, &cache).unwrap();
+    assert_eq!(*target.slideshow.borrow(), Some(slideshow));
+    assert_eq!(WindowsWallpaper::get_current(target_app).unwrap(), captured);
+}
+
+#[test]
+fn test_wallpaper_fit_registry_values() {
+    for fit in [
+        WallpaperFit::Fill,
+        WallpaperFit::Fit,
+        WallpaperFit::Stretch,
+        WallpaperFit::Tile,
+        WallpaperFit::Center,
+        WallpaperFit::Span,
+    ] {
+        let (style, tile) = fit.registry_values();
+        assert_eq!(WallpaperFit::from_registry_values(style, tile), Some(fit));
+    }
+}
+
+#[test]
+fn test_registry_color_byte_order() {
+    // Windows' default blue, #0078D7
+    let blue = RgbaHexColor::new(0x00, 0x78, 0xd7, 0xff);
+    assert_eq!(RgbaHexColor::from_abgr(0xffd77800), blue);
+    assert_eq!(RgbaHexColor::from_argb(0xff0078d7), blue);
+    assert_eq!(blue.to_abgr(), 0xffd77800);
+    assert_eq!(blue.to_argb(), 0xff0078d7);
+}
+
+#[test]
+fn test_capture_color_scheme() {
+    let (system, app) = FakeWindowsSystem::new_app();
+    for (path, name, value) in [
+        (WindowsColorScheme::PERSONALIZE_KEY, "AppsUseLightTheme", 0),
+        (
+            WindowsColorScheme::PERSONALIZE_KEY,
+            "SystemUsesLightTheme",
+            1,
+        ),
+        (WindowsColorScheme::DWM_KEY, "ColorizationColor", 0xc40078d7),
+    ] {
+        system
+            .set_registry_value(path, name, RegistryValue::Dword(value))
+            .unwrap();
+    }
+
+    let current = WindowsColorScheme::get_current(app.clone()).unwrap();
+    assert_eq!(current.app_theme, Some(ColorTheme::Dark));
+    assert_eq!(current.system_color_theme, Some(ColorTheme::Light));
+    assert_eq!(
+        current.accent_color,
+        Some(RgbaHexColor::new(0x00, 0x78, 0xd7, 0xc4))
+    );
+
+    // AccentColor wins over the legacy ColorizationColor
+    system
+        .set_registry_value(
+            WindowsColorScheme::DWM_KEY,
+            "AccentColor",
+            RegistryValue::Dword(0xff3a2a1a),
+        )
+        .unwrap();
+    let current = WindowsColorScheme::get_current(app).unwrap();
+    assert_eq!(
+        current.accent_color,
+        Some(RgbaHexColor::new(0x1a, 0x2a, 0x3a, 0xff))
+    );
+}
+
+#[test]
+fn test_accent_palette() {
+    let accent = RgbaHexColor::new(0x00, 0x78, 0xd7, 0xff);
+    let palette = accent_palette(accent);
+
+    assert_eq!(palette[3], accent);
+    assert_eq!(palette[0], RgbaHexColor::new(0x99, 0xc9, 0xef, 0xff));
+    assert_eq!(palette[6], RgbaHexColor::new(0x00, 0x24, 0x41, 0xff));
+    // Gets darker from the lightest shade to the darkest one
+    for shades in palette[..7].windows(2) {
+        assert!(shades[0].0.g > shades[1].0.g);
+    }
+
+    let bytes = accent_palette_bytes(accent);
+    assert_eq!(bytes.len(), 32);
+    assert_eq!(bytes[12..16], [0x00, 0x78, 0xd7, 0x00]);
+}
+
+#[test]
+fn test_apply_accent_color() {
+    let (system, app) = FakeWindowsSystem::new_app();
+    let color_scheme: WindowsColorScheme = serde_json::from_str(
+        r##"{
+            "accent_color": "#0078d7ff",
+            "accent_on_title_bars": true,
+            "accent_on_start_and_taskbar": false
+        }"##,
+    )
+    .unwrap();
+    color_scheme.apply(app).unwrap();
+
+    let accent = RgbaHexColor::new(0x00, 0x78, 0xd7, 0xff);
+    for (key, name, value) in [
+        (
+            WindowsColorScheme::DWM_KEY,
+            "AccentColor",
+            RegistryValue::Dword(0xffd77800),
+        ),
+        (
+            WindowsColorScheme::DWM_KEY,
+            "ColorizationColor",
+            RegistryValue::Dword(0xff0078d7),
+        ),
+        (
+            WindowsColorScheme::DWM_KEY,
+            "ColorPrevalence",
+            RegistryValue::Dword(1),
+        ),
+        (
+            WindowsColorScheme::PERSONALIZE_KEY,
+            "ColorPrevalence",
+            RegistryValue::Dword(0),
+        ),
+        (
+            WindowsColorScheme::ACCENT_KEY,
+            "AccentPalette",
+            RegistryValue::Binary(accent_palette_bytes(accent)),
+        ),
+        (
+            WindowsColorScheme::ACCENT_KEY,
+            "AccentColorMenu",
+            RegistryValue::Dword(accent_palette(accent)[4].to_abgr()),
+        ),
+    ] {
+        assert_eq!(system.get(key, name), Some(value), "{}", name);
+    }
+}
+
+#[test]
+fn test_color_scheme_round_trip() {
+    let (source, source_app) = FakeWindowsSystem::new_app();
+    for (key, name, value) in [
+        (WindowsColorScheme::PERSONALIZE_KEY, "AppsUseLightTheme", 1),
+        (
+            WindowsColorScheme::PERSONALIZE_KEY,
+            "SystemUsesLightTheme",
+            0,
+        ),
+        (WindowsColorScheme::PERSONALIZE_KEY, "ColorPrevalence", 1),
+        (WindowsColorScheme::DWM_KEY, "ColorPrevalence", 0),
+        (WindowsColorScheme::DWM_KEY, "AccentColor", 0xff1a2a3a),
+    ] {
+        source
+            .set_registry_value(key, name, RegistryValue::Dword(value))
+            .unwrap();
+    }
+    let captured = WindowsColorScheme::get_current(source_app.clone()).unwrap();
+
+    // Applying onto another system captures back the same scheme
+    let (_, target_app) = FakeWindowsSystem::new_app();
+    captured.apply(target_app.clone()).unwrap();
+    assert_eq!(
+        WindowsColorScheme::get_current(target_app).unwrap(),
+        captured
+    );
+
+    // Applying onto the same system leaves what was captured untouched
+    captured.apply(source_app.clone()).unwrap();
+    assert_eq!(
+        WindowsColorScheme::get_current(source_app).unwrap(),
+        captured
+    );
+}
+
+#[test]
+fn test_windows_color_scheme_plan() {
+    let color_scheme: WindowsColorScheme =
+        serde_json::from_str(r#"{ "app_theme": "dark", "system_color_theme": "light" }"#).unwrap();
+
+    assert_eq!(
+        color_scheme.plan(&FakeWindowsSystem::new_app().1).unwrap(),
+        vec![
+            PlannedAction::SetRegistryValue {
+                path: WindowsColorScheme::PERSONALIZE_KEY.to_string(),
+                name: "AppsUseLightTheme".to_string(),
+                value: RegistryValue::Dword(0),
+            },
+            PlannedAction::SetRegistryValue {
+                path: WindowsColorScheme::PERSONALIZE_KEY.to_string(),
+                name: "SystemUsesLightTheme".to_string(),
+                value: RegistryValue::Dword(1),
+            },
+        ]
+    );
+}
@@ -1,10 +1,230 @@
+use crate::{
+    error::{Result, UnhandledError},
+    library::{
+        Library,
+        cache::{AssetSource, Cache},
+    },
+    theme::{
+        ThemeApp,
+        plan::PlannedAction,
+        windows::system::{RegistryValue, Slideshow, Windows, get_windows},
+    },
+};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
-#[derive(Deserialize, Serialize, JsonSchema)]
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
 #[serde(tag = "type")]
 #[serde(rename_all = "lowercase")]
 pub enum WindowsWallpaper {
-    Single { url: String },
-    Diaporama { urls: Vec<String> },
+    Single {
+        /// http(s) or `file://` url, absolute path or path relative to the theme file
+        url: String,
+        fit: Option<WallpaperFit>,
+    },
+    Diaporama {
+        #[serde(default)]
+        urls: Vec<String>,
+        /// Existing folder of images to use instead of `urls`
+        folder: Option<String>,
+        /// Seconds between two pictures
+        interval: Option<u64>,
+        shuffle: Option<bool>,
+        fit: Option<WallpaperFit>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum WallpaperFit {
+    Fill,
+    Fit,
+    Stretch,
+    Tile,
+    Center,
+    Span,
+}
+
+impl WallpaperFit {
+    /// `WallpaperStyle` and `TileWallpaper` values under [`WindowsWallpaper::DESKTOP_KEY`]
+    pub fn registry_values(self) -> (&'static str, &'static str) {
+        match self {
+            WallpaperFit::Fill => ("10", "0"),
+            WallpaperFit::Fit => ("6", "0"),
+            WallpaperFit::Stretch => ("2", "0"),
+            WallpaperFit::Tile => ("0", "1"),
+            WallpaperFit::Center => ("0", "0"),
+            WallpaperFit::Span => ("22", "0"),
+        }
+    }
+
+    pub fn from_registry_values(style: &str, tile: &str) -> Option<Self> {
+        Some(match (style, tile) {
+            ("10", _) => WallpaperFit::Fill,
+            ("6", _) => WallpaperFit::Fit,
+            ("2", _) => WallpaperFit::Stretch,
+            ("0", "1") => WallpaperFit::Tile,
+            ("0", _) => WallpaperFit::Center,
+            ("22", _) => WallpaperFit::Span,
+            _ => return None,
+        })
+    }
+}
+
+impl WindowsWallpaper {
+    pub const DESKTOP_KEY: &str = "Control Panel\\Desktop";
+    pub const DEFAULT_INTERVAL: u64 = 30 * 60;
+
+    fn fit(&self) -> Option<WallpaperFit> {
+        match self {
+            WindowsWallpaper::Single { fit, .. } | WindowsWallpaper::Diaporama { fit, .. } => *fit,
+        }
+    }
+
+    fn fit_registry_values(&self) -> Vec<(&'static str, RegistryValue)> {
+        let Some((style, tile)) = self.fit().map(|fit| fit.registry_values()) else {
+            return vec![];
+        };
+        vec![
+            ("WallpaperStyle", RegistryValue::String(style.to_string())),
+            ("TileWallpaper", RegistryValue::String(tile.to_string())),
+        ]
+    }
+
+    fn read_fit(app: &Windows) -> Result<Option<WallpaperFit>> {
+        let read = |name| -> Result<Option<String>> {
+            Ok(match app.get_registry_value(Self::DESKTOP_KEY, name)? {
+                Some(RegistryValue::String(value)) => Some(value),
+                _ => None,
+            })
+        };
+
+        Ok(match (read("WallpaperStyle")?, read("TileWallpaper")?) {
+            (Some(style), tile) => {
+                WallpaperFit::from_registry_values(&style, tile.as_deref().unwrap_or("0"))
+            }
+            _ => None,
+        })
+    }
+
+    /// A diaporama without pictures would start a slideshow of an empty folder
+    fn check_pictures(&self) -> Result<()> {
+        match self {
+            WindowsWallpaper::Diaporama {
+                urls, folder: None, ..
+            } if urls.is_empty() => Err(UnhandledError::EmptyDiaporama)?,
+            _ => Ok(()),
+        }
+    }
+
+    pub fn resolve_assets(&mut self, base_dir: &Path) -> Result<()> {
+        self.check_pictures()?;
+        match self {
+            WindowsWallpaper::Single { url, .. } => AssetSource::resolve(url, base_dir),
+            WindowsWallpaper::Diaporama { urls, folder, .. } => {
+                for url in urls.iter_mut().chain(folder.iter_mut()) {
+                    AssetSource::resolve(url, base_dir)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Fetches the pictures into `cache` then sets them as wallpaper
+    pub fn apply_with_cache(&self, app: &Windows, cache: &Cache) -> Result<()> {
+        self.check_pictures()?;
+        // The fit is read by windows when the wallpaper gets set, so it goes first
+        for (name, value) in self.fit_registry_values() {
+            app.set_registry_value(Self::DESKTOP_KEY, name, value)?;
+        }
+
+        match self {
+            WindowsWallpaper::Single { url, .. } => app.set_wallpaper(&cache.fetch(url)?),
+            WindowsWallpaper::Diaporama {
+                urls,
+                folder,
+                interval,
+                shuffle,
+                ..
+            } => app.set_slideshow(&Slideshow {
+                folder: match folder {
+                    Some(folder) => PathBuf::from(folder),
+                    None => cache.fetch_folder(urls)?,
+                },
+                interval: Duration::from_secs(interval.unwrap_or(Self::DEFAULT_INTERVAL)),
+                shuffle: shuffle.unwrap_or(false),
+            }),
+        }
+    }
+
+    /// The current wallpaper, `None` if the desktop has no picture
+    pub fn capture(app: &Windows) -> Result<Option<Self>> {
+        let fit = Self::read_fit(app)?;
+
+        if let Some(slideshow) = app.get_slideshow()? {
+            return Ok(Some(WindowsWallpaper::Diaporama {
+                urls: vec![],
+                folder: Some(slideshow.folder.to_string_lossy().to_string()),
+                interval: Some(slideshow.interval.as_secs()),
+                shuffle: Some(slideshow.shuffle),
+                fit,
+            }));
+        }
+
+        Ok(app.get_wallpaper()?.map(|path| WindowsWallpaper::Single {
+            url: path.to_string_lossy().to_string(),
+            fit,
+        }))
+    }
+}
+
+impl ThemeApp for WindowsWallpaper {
+    const NAME: &'static str = "Windows wallpaper";
+    type App = Windows;
+
+    fn get_app() -> Result<Option<Self::App>> {
+        Ok(get_windows())
+    }
+
+    fn apply(&self, app: Self::App) -> Result<()> {
+        self.apply_with_cache(&app, &Library::new()?.cache())
+    }
+
+    fn plan(&self, _app: &Self::App) -> Result<Vec<PlannedAction>> {
+        self.check_pictures()?;
+        let mut actions: Vec<PlannedAction> = self
+            .fit_registry_values()
+            .into_iter()
+            .map(|(name, value)| PlannedAction::SetRegistryValue {
+                path: Self::DESKTOP_KEY.to_string(),
+                name: name.to_string(),
+                value,
+            })
+            .collect();
+
+        actions.push(PlannedAction::Other(match self {
+            WindowsWallpaper::Single { url, .. } => format!("set desktop wallpaper to {}", url),
+            WindowsWallpaper::Diaporama {
+                urls,
+                folder,
+                interval,
+                ..
+            } => format!(
+                "start a slideshow of {} every {}s",
+                folder
+                    .clone()
+                    .unwrap_or_else(|| format!("{} pictures", urls.len())),
+                interval.unwrap_or(Self::DEFAULT_INTERVAL)
+            ),
+        }));
+        Ok(actions)
+    }
+
+    fn get_current(app: Self::App) -> Result<Self> {
+        Ok(Self::capture(&app)?.ok_or(UnhandledError::NoWallpaper)?)
+    }
 }